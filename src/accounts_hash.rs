@@ -0,0 +1,227 @@
+use {
+    crate::{latest::LatestAccounts, verify::account_hash, SnapshotExtractor},
+    log::info,
+    solana_runtime::snapshot_archive_info::{FullSnapshotArchiveInfo, SnapshotArchiveInfoGetter},
+    solana_sdk::{
+        hash::{Hash, Hasher},
+        pubkey::Pubkey,
+    },
+    std::{fmt, path::Path, time::Instant},
+};
+
+/// Fanout of the Merkle tree the accounts hash is computed over.
+pub const MERKLE_FANOUT: usize = 16;
+
+/// Hashes of all accounts in the final account set, sorted by pubkey.
+pub fn sorted_account_hashes(accounts: &LatestAccounts) -> Vec<(Pubkey, Hash)> {
+    let mut hashes: Vec<(Pubkey, Hash)> = accounts
+        .iter()
+        .filter_map(|(_, handle)| handle.access())
+        .map(|stored| (stored.meta.pubkey, account_hash(&stored)))
+        .collect();
+    hashes.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    hashes
+}
+
+/// Hashes each chunk of `MERKLE_FANOUT` nodes into a node of the next level.
+pub fn merkle_parent_level(level: &[Hash]) -> Vec<Hash> {
+    level
+        .chunks(MERKLE_FANOUT)
+        .map(|chunk| {
+            let mut hasher = Hasher::default();
+            for hash in chunk {
+                hasher.hash(hash.as_ref());
+            }
+            hasher.result()
+        })
+        .collect()
+}
+
+/// Computes the root of the Merkle tree over `hashes` the way the runtime does:
+/// levels are hashed until a single node remains, so even a single leaf gets hashed once.
+pub fn compute_merkle_root(hashes: Vec<Hash>) -> Hash {
+    if hashes.is_empty() {
+        return Hasher::default().result();
+    }
+    let mut level = hashes;
+    loop {
+        level = merkle_parent_level(&level);
+        if level.len() == 1 {
+            return level[0];
+        }
+    }
+}
+
+/// Computes the accounts hash of the final account set of a full snapshot.
+pub fn accounts_hash(accounts: &LatestAccounts) -> Hash {
+    let started = Instant::now();
+    let hashes = sorted_account_hashes(accounts);
+    let hashed = Instant::now();
+    let root = compute_merkle_root(hashes.into_iter().map(|(_, hash)| hash).collect());
+    info!(
+        "Computed accounts hash {} over {} accounts in {:?} (account hashes {:?})",
+        root,
+        accounts.len(),
+        started.elapsed(),
+        hashed - started
+    );
+    root
+}
+
+/// The hash in snapshot archive file names: the accounts hash, combined with the epoch accounts
+/// hash when the snapshot includes one.
+pub fn snapshot_hash(accounts_hash: &Hash, epoch_accounts_hash: Option<&Hash>) -> Hash {
+    match epoch_accounts_hash {
+        None => *accounts_hash,
+        Some(epoch_accounts_hash) => {
+            let mut hasher = Hasher::default();
+            hasher.hash(accounts_hash.as_ref());
+            hasher.hash(epoch_accounts_hash.as_ref());
+            hasher.result()
+        }
+    }
+}
+
+/// Parses the snapshot hash from a `snapshot-<slot>-<hash>.tar.zst` file name.
+pub fn archive_snapshot_hash(path: &Path) -> Option<Hash> {
+    FullSnapshotArchiveInfo::new_from_path(path.to_path_buf())
+        .ok()
+        .map(|info| info.hash().0)
+}
+
+/// Result of comparing a recomputed accounts hash with the values recorded for the snapshot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountsHashVerification {
+    /// accounts hash recomputed from the account set
+    pub accounts_hash: Hash,
+    /// accounts hash recorded in the `BankHashInfo` of the manifest
+    pub manifest_accounts_hash: Hash,
+    /// epoch accounts hash recorded in the manifest, if any
+    pub epoch_accounts_hash: Option<Hash>,
+    /// snapshot hash derived from the recomputed accounts hash
+    pub snapshot_hash: Hash,
+    /// snapshot hash taken from the archive file name, if known
+    pub archive_snapshot_hash: Option<Hash>,
+}
+
+impl AccountsHashVerification {
+    /// Compares the accounts hash of `accounts` with the manifest of `extractor` and,
+    /// if given, with the snapshot hash from the archive file name.
+    /// Only full snapshots can be verified: the manifest of an incremental snapshot records a
+    /// hash over its own slot range instead.
+    pub fn new(
+        accounts: &LatestAccounts,
+        extractor: &impl SnapshotExtractor,
+        archive_snapshot_hash: Option<Hash>,
+    ) -> Self {
+        let accounts_hash = accounts_hash(accounts);
+        let epoch_accounts_hash = extractor.extra_fields().epoch_accounts_hash;
+        AccountsHashVerification {
+            accounts_hash,
+            manifest_accounts_hash: extractor
+                .accounts_db_fields()
+                .bank_hash_info()
+                .snapshot_hash,
+            epoch_accounts_hash,
+            snapshot_hash: snapshot_hash(&accounts_hash, epoch_accounts_hash.as_ref()),
+            archive_snapshot_hash,
+        }
+    }
+
+    pub fn matches_manifest(&self) -> bool {
+        self.accounts_hash == self.manifest_accounts_hash
+    }
+
+    pub fn matches_archive(&self) -> bool {
+        self.archive_snapshot_hash
+            .is_none_or(|archive_snapshot_hash| archive_snapshot_hash == self.snapshot_hash)
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.matches_manifest() && self.matches_archive()
+    }
+}
+
+impl fmt::Display for AccountsHashVerification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "accounts hash {} (manifest {}), snapshot hash {}",
+            self.accounts_hash, self.manifest_accounts_hash, self.snapshot_hash
+        )?;
+        if let Some(archive_snapshot_hash) = self.archive_snapshot_hash {
+            write!(f, " (archive {})", archive_snapshot_hash)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_accounts_db::{
+            accounts_hash::{AccountsHash, AccountsHashKind, AccountsHasher},
+            epoch_accounts_hash::EpochAccountsHash,
+        },
+        solana_runtime::snapshot_hash::SnapshotHash,
+        solana_sdk::hash::hashv,
+    };
+
+    fn leaves(count: usize) -> Vec<Hash> {
+        (0..count as u64)
+            .map(|i| hashv(&[&i.to_le_bytes()]))
+            .collect()
+    }
+
+    #[test]
+    fn merkle_root_of_17_leaves() {
+        let leaves = leaves(17);
+        let full_chunk = hashv(&leaves[..16].iter().map(Hash::as_ref).collect::<Vec<_>>());
+        let last_chunk = hashv(&[leaves[16].as_ref()]);
+        let expected = hashv(&[full_chunk.as_ref(), last_chunk.as_ref()]);
+        assert_eq!(compute_merkle_root(leaves), expected);
+    }
+
+    #[test]
+    fn merkle_root_matches_runtime() {
+        for count in [0, 1, 2, 15, 16, 17, 255, 256, 257, 4097] {
+            let leaves = leaves(count);
+            assert_eq!(
+                compute_merkle_root(leaves.clone()),
+                AccountsHasher::compute_merkle_root_recurse(leaves, MERKLE_FANOUT),
+                "{} leaves",
+                count
+            );
+        }
+    }
+
+    #[test]
+    fn merkle_root_of_no_leaves() {
+        assert_eq!(compute_merkle_root(Vec::new()), Hasher::default().result());
+    }
+
+    #[test]
+    fn snapshot_hash_without_epoch_accounts_hash() {
+        let accounts_hash = hashv(&[b"accounts"]);
+        assert_eq!(snapshot_hash(&accounts_hash, None), accounts_hash);
+    }
+
+    #[test]
+    fn snapshot_hash_with_epoch_accounts_hash() {
+        let accounts_hash = hashv(&[b"accounts"]);
+        let epoch_accounts_hash = hashv(&[b"epoch accounts"]);
+        let expected = SnapshotHash::new(
+            &AccountsHashKind::Full(AccountsHash(accounts_hash)),
+            Some(&EpochAccountsHash::new(epoch_accounts_hash)),
+        );
+        assert_eq!(
+            snapshot_hash(&accounts_hash, Some(&epoch_accounts_hash)),
+            expected.0
+        );
+        assert_eq!(
+            expected.0,
+            hashv(&[accounts_hash.as_ref(), epoch_accounts_hash.as_ref()])
+        );
+    }
+}
@@ -0,0 +1,1139 @@
+use {
+    crate::{append_vec::StoredAccountMeta, latest::LatestAccounts},
+    serde_json::{Map, Value},
+    solana_sdk::{hash::hash, pubkey::Pubkey},
+    std::{collections::HashMap, fs, path::Path, str::FromStr},
+    thiserror::Error,
+};
+
+#[derive(Error, Debug)]
+pub enum IdlError {
+    #[error("{0}")]
+    IOError(#[from] std::io::Error),
+    #[error("Failed to parse IDL JSON: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Invalid IDL: {0}")]
+    InvalidIdl(String),
+    #[error("IDL has no program address")]
+    MissingAddress,
+    #[error("Unknown type {0}")]
+    UnknownType(String),
+    #[error("Account data ends at offset {0}")]
+    UnexpectedEof(usize),
+    #[error("Invalid {0} at offset {1}")]
+    InvalidData(&'static str, usize),
+}
+
+pub type IdlResult<T> = Result<T, IdlError>;
+
+/// A type of an IDL field.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IdlType {
+    Bool,
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+    U64,
+    I64,
+    F64,
+    U128,
+    I128,
+    U256,
+    I256,
+    Bytes,
+    String,
+    Pubkey,
+    Option(Box<IdlType>),
+    /// `COption` of the SPL programs: a u32 tag followed by the value, also when absent
+    COption(Box<IdlType>),
+    Vec(Box<IdlType>),
+    Array(Box<IdlType>, IdlArrayLen),
+    Defined {
+        name: String,
+        generics: Vec<IdlGenericArg>,
+    },
+    Generic(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IdlArrayLen {
+    Value(usize),
+    Generic(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IdlGenericArg {
+    Type(IdlType),
+    Const(usize),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IdlFields {
+    Named(Vec<(String, IdlType)>),
+    Tuple(Vec<IdlType>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IdlTypeDefTy {
+    Struct(IdlFields),
+    Enum(Vec<(String, IdlFields)>),
+    Alias(IdlType),
+}
+
+/// How a defined type is laid out in account data.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IdlSerialization {
+    #[default]
+    Borsh,
+    /// `#[zero_copy]` types, laid out like `#[repr(C)]` structs
+    Bytemuck { packed: bool },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdlTypeDef {
+    pub name: String,
+    /// names of the generic parameters, in order
+    pub generics: Vec<String>,
+    pub serialization: IdlSerialization,
+    pub ty: IdlTypeDefTy,
+}
+
+/// An account type of the IDL and the discriminator its data starts with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdlAccount {
+    pub name: String,
+    pub discriminator: Vec<u8>,
+}
+
+/// The accounts and types of an Anchor IDL, in the format of Anchor 0.30 and newer or the
+/// legacy format of earlier versions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnchorIdl {
+    name: String,
+    address: Option<Pubkey>,
+    accounts: Vec<IdlAccount>,
+    types: HashMap<String, IdlTypeDef>,
+}
+
+/// An account decoded with an IDL.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedAccount {
+    /// name of the account type in the IDL
+    pub account_type: String,
+    pub data: Value,
+}
+
+impl AnchorIdl {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> IdlResult<Self> {
+        Self::from_json(&serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    pub fn from_json(idl: &Value) -> IdlResult<Self> {
+        let idl = as_object(idl, "IDL")?;
+        // Legacy IDLs have the program name at the top level and the address in the metadata.
+        let legacy = idl.contains_key("name");
+        let name = if legacy {
+            idl.get("name")
+        } else {
+            idl.get("metadata")
+                .and_then(|metadata| metadata.get("name"))
+        }
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+        let address = if legacy {
+            idl.get("metadata")
+                .and_then(|metadata| metadata.get("address"))
+        } else {
+            idl.get("address")
+        }
+        .and_then(Value::as_str)
+        .map(|address| {
+            Pubkey::from_str(address)
+                .map_err(|_| IdlError::InvalidIdl(format!("program address {}", address)))
+        })
+        .transpose()?;
+
+        let mut types = HashMap::new();
+        for ty in as_array_or_empty(idl.get("types"))? {
+            let ty = parse_type_def(ty)?;
+            types.insert(ty.name.clone(), ty);
+        }
+        let mut accounts = Vec::new();
+        for account in as_array_or_empty(idl.get("accounts"))? {
+            let account_object = as_object(account, "account")?;
+            let name = as_str(account_object.get("name"), "account name")?.to_string();
+            // Legacy IDLs define the account types inline.
+            if account_object.contains_key("type") {
+                let ty = parse_type_def(account)?;
+                types.insert(ty.name.clone(), ty);
+            }
+            let discriminator = match account_object.get("discriminator") {
+                Some(discriminator) => as_array(discriminator, "discriminator")?
+                    .iter()
+                    .map(|byte| {
+                        byte.as_u64()
+                            .and_then(|byte| u8::try_from(byte).ok())
+                            .ok_or_else(|| {
+                                IdlError::InvalidIdl(format!("discriminator of {}", name))
+                            })
+                    })
+                    .collect::<IdlResult<Vec<u8>>>()?,
+                None => account_discriminator(&name).to_vec(),
+            };
+            accounts.push(IdlAccount {
+                name,
+                discriminator,
+            });
+        }
+
+        Ok(AnchorIdl {
+            name,
+            address,
+            accounts,
+            types,
+        })
+    }
+
+    /// Name of the program.
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Address of the program, which legacy IDLs only contain once deployed.
+    pub const fn address(&self) -> Option<&Pubkey> {
+        self.address.as_ref()
+    }
+
+    pub fn accounts(&self) -> &[IdlAccount] {
+        self.accounts.as_slice()
+    }
+
+    pub fn type_def(&self, name: &str) -> Option<&IdlTypeDef> {
+        self.types.get(name)
+    }
+
+    /// Finds the account type whose discriminator the data starts with.
+    pub fn account_by_discriminator(&self, data: &[u8]) -> Option<&IdlAccount> {
+        self.accounts.iter().find(|account| {
+            !account.discriminator.is_empty() && data.starts_with(&account.discriminator)
+        })
+    }
+
+    /// Decodes account data into a JSON value following the IDL type of its discriminator.
+    /// Returns None if no account type matches, data after the account type is ignored.
+    pub fn decode_account(&self, data: &[u8]) -> Option<IdlResult<DecodedAccount>> {
+        let account = self.account_by_discriminator(data)?;
+        let mut decoder = Decoder {
+            idl: self,
+            data,
+            offset: account.discriminator.len(),
+            depth: 0,
+        };
+        let ty = IdlType::Defined {
+            name: account.name.clone(),
+            generics: Vec::new(),
+        };
+        Some(
+            decoder
+                .decode(&ty, &HashMap::new())
+                .map(|data| DecodedAccount {
+                    account_type: account.name.clone(),
+                    data,
+                }),
+        )
+    }
+}
+
+/// IDLs by program address, to decode accounts of any of the programs.
+#[derive(Clone, Debug, Default)]
+pub struct AnchorIdls {
+    idls: HashMap<Pubkey, AnchorIdl>,
+}
+
+impl AnchorIdls {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an IDL for `program_id`, replacing any IDL added for the program before.
+    pub fn insert(&mut self, program_id: Pubkey, idl: AnchorIdl) {
+        self.idls.insert(program_id, idl);
+    }
+
+    /// Adds an IDL for the program address it contains.
+    pub fn insert_with_address(&mut self, idl: AnchorIdl) -> IdlResult<()> {
+        let program_id = *idl.address().ok_or(IdlError::MissingAddress)?;
+        self.insert(program_id, idl);
+        Ok(())
+    }
+
+    pub fn get(&self, program_id: &Pubkey) -> Option<&AnchorIdl> {
+        self.idls.get(program_id)
+    }
+
+    pub fn program_ids(&self) -> impl Iterator<Item = &Pubkey> {
+        self.idls.keys()
+    }
+
+    /// Decodes an account owned by one of the programs.
+    pub fn decode(&self, stored: &StoredAccountMeta) -> Option<IdlResult<DecodedAccount>> {
+        self.get(&stored.account_meta.owner)?
+            .decode_account(stored.data)
+    }
+
+    /// Iterates over the latest copy of every account owned by one of the programs whose
+    /// discriminator is known, in no particular order.
+    pub fn decode_accounts<'a>(
+        &'a self,
+        accounts: &'a LatestAccounts,
+    ) -> impl Iterator<Item = (StoredAccountMeta<'a>, IdlResult<DecodedAccount>)> + 'a {
+        accounts.iter().filter_map(|(_, handle)| {
+            let stored = handle.access()?;
+            let decoded = self.decode(&stored)?;
+            Some((stored, decoded))
+        })
+    }
+}
+
+/// The discriminator Anchor derives from the name of an account type.
+pub fn account_discriminator(name: &str) -> [u8; 8] {
+    let hash = hash(format!("account:{}", name).as_bytes());
+    let mut discriminator = [0; 8];
+    discriminator.copy_from_slice(&hash.as_ref()[..8]);
+    discriminator
+}
+
+fn as_object<'a>(value: &'a Value, what: &str) -> IdlResult<&'a Map<String, Value>> {
+    value
+        .as_object()
+        .ok_or_else(|| IdlError::InvalidIdl(format!("{} is not an object", what)))
+}
+
+fn as_array<'a>(value: &'a Value, what: &str) -> IdlResult<&'a Vec<Value>> {
+    value
+        .as_array()
+        .ok_or_else(|| IdlError::InvalidIdl(format!("{} is not an array", what)))
+}
+
+fn as_array_or_empty(value: Option<&Value>) -> IdlResult<&[Value]> {
+    match value {
+        Some(value) => Ok(as_array(value, "list")?.as_slice()),
+        None => Ok(&[]),
+    }
+}
+
+fn as_str<'a>(value: Option<&'a Value>, what: &str) -> IdlResult<&'a str> {
+    value
+        .and_then(Value::as_str)
+        .ok_or_else(|| IdlError::InvalidIdl(format!("missing {}", what)))
+}
+
+fn as_usize(value: &Value, what: &str) -> IdlResult<usize> {
+    value
+        .as_u64()
+        .map(|value| value as usize)
+        .or_else(|| value.as_str().and_then(|value| value.parse().ok()))
+        .ok_or_else(|| IdlError::InvalidIdl(format!("{} is not a number", what)))
+}
+
+fn parse_type_def(ty: &Value) -> IdlResult<IdlTypeDef> {
+    let ty = as_object(ty, "type definition")?;
+    let name = as_str(ty.get("name"), "type name")?.to_string();
+    let generics = as_array_or_empty(ty.get("generics"))?
+        .iter()
+        .map(|generic| Ok(as_str(generic.get("name"), "generic name")?.to_string()))
+        .collect::<IdlResult<Vec<_>>>()?;
+    let serialization = match ty.get("serialization").and_then(Value::as_str) {
+        None | Some("borsh") => IdlSerialization::Borsh,
+        Some("bytemuck") | Some("bytemuckunsafe") => IdlSerialization::Bytemuck {
+            packed: ty
+                .get("repr")
+                .and_then(|repr| repr.get("packed"))
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+        },
+        Some(serialization) => {
+            return Err(IdlError::InvalidIdl(format!(
+                "serialization {} of {}",
+                serialization, name
+            )))
+        }
+    };
+    let body = as_object(
+        ty.get("type")
+            .ok_or_else(|| IdlError::InvalidIdl(format!("missing type of {}", name)))?,
+        "type",
+    )?;
+    let ty = match as_str(body.get("kind"), "type kind")? {
+        "struct" => IdlTypeDefTy::Struct(parse_fields(body.get("fields"))?),
+        "enum" => IdlTypeDefTy::Enum(
+            as_array_or_empty(body.get("variants"))?
+                .iter()
+                .map(|variant| {
+                    Ok((
+                        as_str(variant.get("name"), "variant name")?.to_string(),
+                        parse_fields(variant.get("fields"))?,
+                    ))
+                })
+                .collect::<IdlResult<Vec<_>>>()?,
+        ),
+        // `alias` in the new format, `value` in the legacy one
+        "type" | "alias" => IdlTypeDefTy::Alias(parse_type(
+            body.get("alias")
+                .or_else(|| body.get("value"))
+                .ok_or_else(|| IdlError::InvalidIdl(format!("missing alias of {}", name)))?,
+        )?),
+        kind => {
+            return Err(IdlError::InvalidIdl(format!(
+                "type kind {} of {}",
+                kind, name
+            )))
+        }
+    };
+    Ok(IdlTypeDef {
+        name,
+        generics,
+        serialization,
+        ty,
+    })
+}
+
+/// Parses named fields (objects with a name and type) or tuple fields (types).
+fn parse_fields(fields: Option<&Value>) -> IdlResult<IdlFields> {
+    let fields = as_array_or_empty(fields)?;
+    if fields
+        .first()
+        .is_some_and(|field| field.get("name").is_some() && field.get("type").is_some())
+    {
+        Ok(IdlFields::Named(
+            fields
+                .iter()
+                .map(|field| {
+                    Ok((
+                        as_str(field.get("name"), "field name")?.to_string(),
+                        parse_type(field.get("type").unwrap_or(&Value::Null))?,
+                    ))
+                })
+                .collect::<IdlResult<Vec<_>>>()?,
+        ))
+    } else {
+        Ok(IdlFields::Tuple(
+            fields
+                .iter()
+                .map(parse_type)
+                .collect::<IdlResult<Vec<_>>>()?,
+        ))
+    }
+}
+
+fn parse_type(ty: &Value) -> IdlResult<IdlType> {
+    if let Some(name) = ty.as_str() {
+        return Ok(match name {
+            "bool" => IdlType::Bool,
+            "u8" => IdlType::U8,
+            "i8" => IdlType::I8,
+            "u16" => IdlType::U16,
+            "i16" => IdlType::I16,
+            "u32" => IdlType::U32,
+            "i32" => IdlType::I32,
+            "f32" => IdlType::F32,
+            "u64" => IdlType::U64,
+            "i64" => IdlType::I64,
+            "f64" => IdlType::F64,
+            "u128" => IdlType::U128,
+            "i128" => IdlType::I128,
+            "u256" => IdlType::U256,
+            "i256" => IdlType::I256,
+            "bytes" => IdlType::Bytes,
+            "string" => IdlType::String,
+            // `publicKey` in the legacy format
+            "pubkey" | "publicKey" => IdlType::Pubkey,
+            name => return Err(IdlError::UnknownType(name.to_string())),
+        });
+    }
+    let ty = as_object(ty, "type")?;
+    let inner = |key: &str| parse_type(&ty[key]).map(Box::new);
+    if ty.contains_key("option") {
+        Ok(IdlType::Option(inner("option")?))
+    } else if ty.contains_key("coption") {
+        Ok(IdlType::COption(inner("coption")?))
+    } else if ty.contains_key("vec") {
+        Ok(IdlType::Vec(inner("vec")?))
+    } else if let Some(array) = ty.get("array") {
+        let [element, len] = as_array(array, "array")?.as_slice() else {
+            return Err(IdlError::InvalidIdl("array without length".to_string()));
+        };
+        let len = match len.get("generic") {
+            Some(generic) => {
+                IdlArrayLen::Generic(as_str(Some(generic), "array length")?.to_string())
+            }
+            None => IdlArrayLen::Value(as_usize(len, "array length")?),
+        };
+        Ok(IdlType::Array(Box::new(parse_type(element)?), len))
+    } else if let Some(defined) = ty.get("defined") {
+        // a plain name in the legacy format, an object with optional generics in the new one
+        if let Some(name) = defined.as_str() {
+            return Ok(IdlType::Defined {
+                name: name.to_string(),
+                generics: Vec::new(),
+            });
+        }
+        let generics = as_array_or_empty(defined.get("generics"))?
+            .iter()
+            .map(
+                |generic| match generic.get("kind").and_then(Value::as_str) {
+                    Some("type") => Ok(IdlGenericArg::Type(parse_type(
+                        generic.get("type").unwrap_or(&Value::Null),
+                    )?)),
+                    Some("const") => Ok(IdlGenericArg::Const(as_usize(
+                        generic.get("value").unwrap_or(&Value::Null),
+                        "const generic",
+                    )?)),
+                    _ => Err(IdlError::InvalidIdl("generic argument".to_string())),
+                },
+            )
+            .collect::<IdlResult<Vec<_>>>()?;
+        Ok(IdlType::Defined {
+            name: as_str(defined.get("name"), "defined type name")?.to_string(),
+            generics,
+        })
+    } else if let Some(generic) = ty.get("generic") {
+        Ok(IdlType::Generic(
+            as_str(Some(generic), "generic")?.to_string(),
+        ))
+    } else {
+        Err(IdlError::UnknownType(Value::Object(ty.clone()).to_string()))
+    }
+}
+
+/// Generic arguments in scope, by parameter name.
+type Generics = HashMap<String, IdlGenericArg>;
+
+/// How deep defined types may nest, which stops types defined in terms of themselves.
+const MAX_TYPE_DEPTH: usize = 64;
+
+struct Decoder<'a> {
+    idl: &'a AnchorIdl,
+    data: &'a [u8],
+    offset: usize,
+    /// defined types being decoded
+    depth: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn read(&mut self, len: usize) -> IdlResult<&'a [u8]> {
+        let bytes = self
+            .offset
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.offset..end))
+            .ok_or(IdlError::UnexpectedEof(self.offset))?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> IdlResult<[u8; N]> {
+        Ok(self.read(N)?.try_into().unwrap())
+    }
+
+    fn read_len(&mut self) -> IdlResult<usize> {
+        Ok(u32::from_le_bytes(self.read_array()?) as usize)
+    }
+
+    /// Checks that `len` elements can follow, as every element but a zero-sized one takes at
+    /// least a byte. Rejects lengths that would decode billions of zero-sized elements.
+    const fn check_len(&self, len: usize, what: &'static str, offset: usize) -> IdlResult<()> {
+        if len > self.data.len().saturating_sub(self.offset) {
+            return Err(IdlError::InvalidData(what, offset));
+        }
+        Ok(())
+    }
+
+    fn check_depth(depth: usize, name: &str) -> IdlResult<()> {
+        if depth >= MAX_TYPE_DEPTH {
+            return Err(IdlError::InvalidIdl(format!(
+                "type {} nests deeper than {} levels",
+                name, MAX_TYPE_DEPTH
+            )));
+        }
+        Ok(())
+    }
+
+    fn resolve<'b>(&self, ty: &'b IdlType, generics: &'b Generics) -> IdlResult<&'b IdlType> {
+        match ty {
+            IdlType::Generic(name) => match generics.get(name) {
+                Some(IdlGenericArg::Type(ty)) => Ok(ty),
+                _ => Err(IdlError::UnknownType(name.clone())),
+            },
+            ty => Ok(ty),
+        }
+    }
+
+    fn array_len(len: &IdlArrayLen, generics: &Generics) -> IdlResult<usize> {
+        match len {
+            IdlArrayLen::Value(len) => Ok(*len),
+            IdlArrayLen::Generic(name) => match generics.get(name) {
+                Some(IdlGenericArg::Const(len)) => Ok(*len),
+                _ => Err(IdlError::UnknownType(name.clone())),
+            },
+        }
+    }
+
+    /// Looks up a defined type and binds its generic parameters to the arguments.
+    fn type_def(
+        &self,
+        name: &str,
+        args: &[IdlGenericArg],
+        generics: &Generics,
+    ) -> IdlResult<(&'a IdlTypeDef, Generics)> {
+        let type_def = self
+            .idl
+            .type_def(name)
+            .ok_or_else(|| IdlError::UnknownType(name.to_string()))?;
+        let bound = type_def
+            .generics
+            .iter()
+            .zip(args)
+            .map(|(param, arg)| {
+                let arg = match arg {
+                    IdlGenericArg::Type(ty) => {
+                        IdlGenericArg::Type(self.resolve(ty, generics)?.clone())
+                    }
+                    IdlGenericArg::Const(len) => IdlGenericArg::Const(*len),
+                };
+                Ok((param.clone(), arg))
+            })
+            .collect::<IdlResult<Generics>>()?;
+        Ok((type_def, bound))
+    }
+
+    fn decode(&mut self, ty: &IdlType, generics: &Generics) -> IdlResult<Value> {
+        let offset = self.offset;
+        Ok(match self.resolve(ty, generics)? {
+            IdlType::Bool => match self.read(1)?[0] {
+                0 => Value::Bool(false),
+                1 => Value::Bool(true),
+                _ => return Err(IdlError::InvalidData("bool", offset)),
+            },
+            IdlType::U8 => Value::from(self.read(1)?[0]),
+            IdlType::I8 => Value::from(self.read(1)?[0] as i8),
+            IdlType::U16 => Value::from(u16::from_le_bytes(self.read_array()?)),
+            IdlType::I16 => Value::from(i16::from_le_bytes(self.read_array()?)),
+            IdlType::U32 => Value::from(u32::from_le_bytes(self.read_array()?)),
+            IdlType::I32 => Value::from(i32::from_le_bytes(self.read_array()?)),
+            IdlType::F32 => Value::from(f32::from_le_bytes(self.read_array()?)),
+            IdlType::U64 => Value::from(u64::from_le_bytes(self.read_array()?)),
+            IdlType::I64 => Value::from(i64::from_le_bytes(self.read_array()?)),
+            IdlType::F64 => Value::from(f64::from_le_bytes(self.read_array()?)),
+            // JSON numbers cannot hold 128-bit integers without losing precision
+            IdlType::U128 => Value::from(u128::from_le_bytes(self.read_array()?).to_string()),
+            IdlType::I128 => Value::from(i128::from_le_bytes(self.read_array()?).to_string()),
+            IdlType::U256 | IdlType::I256 => {
+                let bytes = self.read(32)?;
+                Value::from(
+                    bytes
+                        .iter()
+                        .rev()
+                        .map(|byte| format!("{:02x}", byte))
+                        .collect::<String>(),
+                )
+            }
+            IdlType::Bytes => {
+                let len = self.read_len()?;
+                Value::from(self.read(len)?.to_vec())
+            }
+            IdlType::String => {
+                let len = self.read_len()?;
+                let string = std::str::from_utf8(self.read(len)?)
+                    .map_err(|_| IdlError::InvalidData("string", offset))?;
+                Value::from(string)
+            }
+            IdlType::Pubkey => Value::from(Pubkey::new_from_array(self.read_array()?).to_string()),
+            IdlType::Option(inner) => match self.read(1)?[0] {
+                0 => Value::Null,
+                1 => self.decode(inner, generics)?,
+                _ => return Err(IdlError::InvalidData("option", offset)),
+            },
+            IdlType::COption(inner) => {
+                let tag = u32::from_le_bytes(self.read_array()?);
+                let value = self.decode(inner, generics)?;
+                match tag {
+                    0 => Value::Null,
+                    1 => value,
+                    _ => return Err(IdlError::InvalidData("coption", offset)),
+                }
+            }
+            IdlType::Vec(inner) => {
+                let len = self.read_len()?;
+                self.check_len(len, "vec length", offset)?;
+                Value::Array(
+                    (0..len)
+                        .map(|_| self.decode(inner, generics))
+                        .collect::<IdlResult<_>>()?,
+                )
+            }
+            IdlType::Array(inner, len) => {
+                let len = Self::array_len(len, generics)?;
+                self.check_len(len, "array length", offset)?;
+                Value::Array(
+                    (0..len)
+                        .map(|_| self.decode(inner, generics))
+                        .collect::<IdlResult<_>>()?,
+                )
+            }
+            IdlType::Defined {
+                name,
+                generics: args,
+            } => {
+                Self::check_depth(self.depth, name)?;
+                let (type_def, bound) = self.type_def(name, args, generics)?;
+                self.depth += 1;
+                let value = self.decode_defined(type_def, &bound);
+                self.depth -= 1;
+                value?
+            }
+            IdlType::Generic(name) => return Err(IdlError::UnknownType(name.clone())),
+        })
+    }
+
+    fn decode_defined(&mut self, type_def: &IdlTypeDef, generics: &Generics) -> IdlResult<Value> {
+        if let IdlSerialization::Bytemuck { packed } = type_def.serialization {
+            return self.decode_repr_c(type_def, packed, generics);
+        }
+        match &type_def.ty {
+            IdlTypeDefTy::Struct(fields) => self.decode_fields(fields, generics),
+            IdlTypeDefTy::Enum(variants) => {
+                let offset = self.offset;
+                let (name, fields) = variants
+                    .get(self.read(1)?[0] as usize)
+                    .ok_or(IdlError::InvalidData("enum variant", offset))?;
+                Ok(match fields {
+                    IdlFields::Tuple(types) if types.is_empty() => Value::from(name.as_str()),
+                    fields => {
+                        let mut variant = Map::new();
+                        variant.insert(name.clone(), self.decode_fields(fields, generics)?);
+                        Value::Object(variant)
+                    }
+                })
+            }
+            IdlTypeDefTy::Alias(ty) => self.decode(ty, generics),
+        }
+    }
+
+    fn decode_fields(&mut self, fields: &IdlFields, generics: &Generics) -> IdlResult<Value> {
+        Ok(match fields {
+            IdlFields::Named(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(name, ty)| Ok((name.clone(), self.decode(ty, generics)?)))
+                    .collect::<IdlResult<_>>()?,
+            ),
+            IdlFields::Tuple(types) => Value::Array(
+                types
+                    .iter()
+                    .map(|ty| self.decode(ty, generics))
+                    .collect::<IdlResult<_>>()?,
+            ),
+        })
+    }
+
+    /// Decodes a zero-copy struct, aligning each field like `#[repr(C)]` unless packed.
+    fn decode_repr_c(
+        &mut self,
+        type_def: &IdlTypeDef,
+        packed: bool,
+        generics: &Generics,
+    ) -> IdlResult<Value> {
+        let start = self.offset;
+        let IdlTypeDefTy::Struct(fields) = &type_def.ty else {
+            return match &type_def.ty {
+                IdlTypeDefTy::Alias(ty) => self.decode(ty, generics),
+                _ => Err(IdlError::InvalidIdl(format!(
+                    "zero-copy type {} is not a struct",
+                    type_def.name
+                ))),
+            };
+        };
+        let types: Vec<&IdlType> = match fields {
+            IdlFields::Named(fields) => fields.iter().map(|(_, ty)| ty).collect(),
+            IdlFields::Tuple(types) => types.iter().collect(),
+        };
+        let mut values = Vec::with_capacity(types.len());
+        let mut struct_align = 1;
+        for ty in types {
+            if !packed {
+                let align = self.align_of(ty, generics, self.depth)?;
+                struct_align = struct_align.max(align);
+                self.offset = start + (self.offset - start).next_multiple_of(align);
+            }
+            values.push(self.decode(ty, generics)?);
+        }
+        self.offset = start + (self.offset - start).next_multiple_of(struct_align);
+        Ok(match fields {
+            IdlFields::Named(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(name, _)| name.clone())
+                    .zip(values)
+                    .collect(),
+            ),
+            IdlFields::Tuple(_) => Value::Array(values),
+        })
+    }
+
+    /// Alignment of a type in a zero-copy struct as compiled for SBF, which aligns 128-bit
+    /// integers to 8 bytes. `depth` counts the defined types it is nested in.
+    fn align_of(&self, ty: &IdlType, generics: &Generics, depth: usize) -> IdlResult<usize> {
+        Ok(match self.resolve(ty, generics)? {
+            IdlType::Bool | IdlType::U8 | IdlType::I8 | IdlType::Pubkey => 1,
+            IdlType::U16 | IdlType::I16 => 2,
+            IdlType::U32 | IdlType::I32 | IdlType::F32 => 4,
+            IdlType::U64
+            | IdlType::I64
+            | IdlType::F64
+            | IdlType::U128
+            | IdlType::I128
+            | IdlType::U256
+            | IdlType::I256 => 8,
+            IdlType::Array(inner, _) | IdlType::COption(inner) => {
+                self.align_of(inner, generics, depth)?
+            }
+            IdlType::Defined {
+                name,
+                generics: args,
+            } => {
+                Self::check_depth(depth, name)?;
+                let (type_def, bound) = self.type_def(name, args, generics)?;
+                let depth = depth + 1;
+                match (&type_def.serialization, &type_def.ty) {
+                    (IdlSerialization::Bytemuck { packed: true }, _) => 1,
+                    (_, IdlTypeDefTy::Struct(IdlFields::Named(fields))) => fields
+                        .iter()
+                        .map(|(_, ty)| self.align_of(ty, &bound, depth))
+                        .try_fold(1, |align, field| Ok::<_, IdlError>(align.max(field?)))?,
+                    (_, IdlTypeDefTy::Struct(IdlFields::Tuple(types))) => types
+                        .iter()
+                        .map(|ty| self.align_of(ty, &bound, depth))
+                        .try_fold(1, |align, field| Ok::<_, IdlError>(align.max(field?)))?,
+                    (_, IdlTypeDefTy::Alias(ty)) => self.align_of(ty, &bound, depth)?,
+                    (_, IdlTypeDefTy::Enum(_)) => 1,
+                }
+            }
+            IdlType::Bytes | IdlType::String | IdlType::Option(_) | IdlType::Vec(_) => 1,
+            IdlType::Generic(name) => return Err(IdlError::UnknownType(name.clone())),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, serde_json::json};
+
+    const DISCRIMINATOR: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
+    /// An IDL with the account type `Account` and the other `types`.
+    fn idl(account: Value, types: Value) -> AnchorIdl {
+        let mut types = types.as_array().unwrap().clone();
+        types.push(json!({ "name": "Account", "type": account }));
+        AnchorIdl::from_json(&json!({
+            "address": Pubkey::new_unique().to_string(),
+            "metadata": { "name": "test" },
+            "accounts": [{ "name": "Account", "discriminator": DISCRIMINATOR }],
+            "types": types,
+        }))
+        .unwrap()
+    }
+
+    fn decode(idl: &AnchorIdl, data: &[u8]) -> IdlResult<Value> {
+        idl.decode_account(&[DISCRIMINATOR.as_slice(), data].concat())
+            .unwrap()
+            .map(|decoded| decoded.data)
+    }
+
+    fn struct_of(field: Value) -> Value {
+        json!({ "kind": "struct", "fields": [{ "name": "field", "type": field }] })
+    }
+
+    #[test]
+    fn decodes_vec() {
+        let idl = idl(struct_of(json!({ "vec": "u16" })), json!([]));
+        let data = [2, 0, 0, 0, 7, 0, 9, 0];
+        assert_eq!(decode(&idl, &data).unwrap(), json!({ "field": [7, 9] }));
+        assert!(matches!(
+            decode(&idl, &data[..6]),
+            Err(IdlError::UnexpectedEof(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_vec_length_beyond_data() {
+        let empty = json!([{ "name": "Empty", "type": { "kind": "struct", "fields": [] } }]);
+        let idl = idl(
+            struct_of(json!({ "vec": { "defined": { "name": "Empty" } } })),
+            empty,
+        );
+        // zero-sized elements take no bytes, but cannot outnumber the remaining bytes
+        assert_eq!(
+            decode(&idl, &[2, 0, 0, 0, 0, 0]).unwrap(),
+            json!({ "field": [[], []] })
+        );
+        assert!(matches!(
+            decode(&idl, &u32::MAX.to_le_bytes()),
+            Err(IdlError::InvalidData("vec length", 8))
+        ));
+    }
+
+    #[test]
+    fn rejects_array_length_beyond_data() {
+        let idl = idl(
+            struct_of(json!({ "array": [{ "array": ["u8", 0] }, 4_000_000_000u64] })),
+            json!([]),
+        );
+        assert!(matches!(
+            decode(&idl, &[]),
+            Err(IdlError::InvalidData("array length", 8))
+        ));
+    }
+
+    #[test]
+    fn rejects_alias_cycles() {
+        let idl = idl(
+            struct_of(json!({ "defined": { "name": "First" } })),
+            json!([
+                { "name": "First", "type": { "kind": "type", "alias": { "defined": { "name": "Second" } } } },
+                { "name": "Second", "type": { "kind": "type", "alias": { "defined": { "name": "First" } } } },
+            ]),
+        );
+        assert!(matches!(
+            decode(&idl, &[0; 8]),
+            Err(IdlError::InvalidIdl(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_self_containing_zero_copy_structs() {
+        let account = struct_of(json!({ "defined": { "name": "Account" } }));
+        let idl = AnchorIdl::from_json(&json!({
+            "address": Pubkey::new_unique().to_string(),
+            "metadata": { "name": "test" },
+            "accounts": [{ "name": "Account", "discriminator": DISCRIMINATOR }],
+            "types": [{ "name": "Account", "serialization": "bytemuck", "type": account }],
+        }))
+        .unwrap();
+        assert!(matches!(
+            decode(&idl, &[0; 8]),
+            Err(IdlError::InvalidIdl(_))
+        ));
+    }
+
+    #[test]
+    fn decodes_recursion_bounded_by_data() {
+        let idl = idl(
+            json!({
+                "kind": "struct",
+                "fields": [{ "name": "next", "type": { "option": { "defined": { "name": "Account" } } } }],
+            }),
+            json!([]),
+        );
+        assert_eq!(
+            decode(&idl, &[1, 1, 0]).unwrap(),
+            json!({ "next": { "next": { "next": null } } })
+        );
+        let nested = vec![1; MAX_TYPE_DEPTH];
+        assert!(matches!(
+            decode(&idl, &nested),
+            Err(IdlError::InvalidIdl(_))
+        ));
+    }
+
+    #[test]
+    fn parses_legacy_idl() {
+        let address = Pubkey::new_unique();
+        let idl = AnchorIdl::from_json(&json!({
+            "version": "0.1.0",
+            "name": "legacy",
+            "metadata": { "address": address.to_string() },
+            "accounts": [{
+                "name": "Counter",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        { "name": "authority", "type": "publicKey" },
+                        { "name": "count", "type": "u64" },
+                        { "name": "state", "type": { "defined": "State" } },
+                    ],
+                },
+            }],
+            "types": [{
+                "name": "State",
+                "type": { "kind": "enum", "variants": [{ "name": "Open" }, { "name": "Closed" }] },
+            }],
+        }))
+        .unwrap();
+        assert_eq!(idl.name(), "legacy");
+        assert_eq!(idl.address(), Some(&address));
+        assert_eq!(
+            idl.accounts(),
+            [IdlAccount {
+                name: "Counter".to_string(),
+                discriminator: account_discriminator("Counter").to_vec(),
+            }]
+        );
+
+        let authority = Pubkey::new_unique();
+        let data = [
+            &account_discriminator("Counter")[..],
+            authority.as_ref(),
+            &5u64.to_le_bytes(),
+            &[1],
+        ]
+        .concat();
+        let decoded = idl.decode_account(&data).unwrap().unwrap();
+        assert_eq!(decoded.account_type, "Counter");
+        assert_eq!(
+            decoded.data,
+            json!({ "authority": authority.to_string(), "count": 5, "state": "Closed" })
+        );
+        assert!(idl.decode_account(&data[8..]).is_none());
+    }
+
+    #[test]
+    fn derives_account_discriminator() {
+        use sha2::{Digest, Sha256};
+
+        let digest = Sha256::digest(b"account:Counter");
+        assert_eq!(account_discriminator("Counter")[..], digest[..8]);
+        assert_ne!(
+            account_discriminator("Counter"),
+            account_discriminator("counter")
+        );
+    }
+
+    #[test]
+    fn decodes_enum_variants_with_fields() {
+        let idl = idl(
+            struct_of(json!({ "vec": { "defined": { "name": "Event" } } })),
+            json!([{
+                "name": "Event",
+                "type": {
+                    "kind": "enum",
+                    "variants": [
+                        { "name": "Empty" },
+                        { "name": "Tuple", "fields": ["u8", "bool"] },
+                        { "name": "Named", "fields": [{ "name": "amount", "type": "u16" }] },
+                    ],
+                },
+            }]),
+        );
+        let data = [3, 0, 0, 0, 0, 1, 7, 1, 2, 9, 0];
+        assert_eq!(
+            decode(&idl, &data).unwrap(),
+            json!({ "field": ["Empty", { "Tuple": [7, true] }, { "Named": { "amount": 9 } }] })
+        );
+        assert!(matches!(
+            decode(&idl, &[1, 0, 0, 0, 3]),
+            Err(IdlError::InvalidData("enum variant", 12))
+        ));
+    }
+
+    #[test]
+    fn decodes_coption() {
+        let idl = idl(
+            json!({
+                "kind": "struct",
+                "fields": [
+                    { "name": "some", "type": { "coption": "u16" } },
+                    { "name": "none", "type": { "coption": "u16" } },
+                    { "name": "last", "type": "u8" },
+                ],
+            }),
+            json!([]),
+        );
+        // the value is stored also when absent
+        let data = [1, 0, 0, 0, 7, 0, 0, 0, 0, 0, 9, 9, 5];
+        assert_eq!(
+            decode(&idl, &data).unwrap(),
+            json!({ "some": 7, "none": null, "last": 5 })
+        );
+        let mut invalid = data;
+        invalid[0] = 2;
+        assert!(matches!(
+            decode(&idl, &invalid),
+            Err(IdlError::InvalidData("coption", 8))
+        ));
+    }
+
+    #[test]
+    fn decodes_zero_copy_alignment_and_padding() {
+        let zero_copy = |name: &str, packed: bool, fields: Value| {
+            json!({
+                "name": name,
+                "serialization": "bytemuck",
+                "repr": { "kind": "c", "packed": packed },
+                "type": { "kind": "struct", "fields": fields },
+            })
+        };
+        let types = json!([
+            zero_copy(
+                "Account",
+                false,
+                json!([
+                    { "name": "flag", "type": "u8" },
+                    { "name": "amount", "type": "u64" },
+                    { "name": "inner", "type": { "defined": { "name": "Inner" } } },
+                    { "name": "last", "type": "u8" },
+                    { "name": "packed", "type": { "defined": { "name": "Packed" } } },
+                ])
+            ),
+            // 20 bytes: the u16 is padded to the alignment of the u32, and so is the end
+            zero_copy(
+                "Inner",
+                false,
+                json!([
+                    { "name": "small", "type": "u16" },
+                    { "name": "values", "type": { "array": ["u32", 3] } },
+                    { "name": "tail", "type": "u8" },
+                ])
+            ),
+            // 9 bytes without any padding
+            zero_copy(
+                "Packed",
+                true,
+                json!([{ "name": "a", "type": "u8" }, { "name": "b", "type": "u64" }])
+            ),
+        ]);
+        let idl = AnchorIdl::from_json(&json!({
+            "address": Pubkey::new_unique().to_string(),
+            "metadata": { "name": "test" },
+            "accounts": [{ "name": "Account", "discriminator": DISCRIMINATOR }],
+            "types": types,
+        }))
+        .unwrap();
+
+        let mut data = vec![0xff; 48];
+        data[0] = 1;
+        data[8..16].copy_from_slice(&2u64.to_le_bytes());
+        data[16..18].copy_from_slice(&3u16.to_le_bytes());
+        for (i, value) in [4u32, 5, 6].iter().enumerate() {
+            data[20 + 4 * i..24 + 4 * i].copy_from_slice(&value.to_le_bytes());
+        }
+        data[32] = 7;
+        data[36] = 8;
+        data[37] = 9;
+        data[38..46].copy_from_slice(&10u64.to_le_bytes());
+        assert_eq!(
+            decode(&idl, &data).unwrap(),
+            json!({
+                "flag": 1,
+                "amount": 2,
+                "inner": { "small": 3, "values": [4, 5, 6], "tail": 7 },
+                "last": 8,
+                "packed": { "a": 9, "b": 10 },
+            })
+        );
+    }
+}
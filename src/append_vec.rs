@@ -16,6 +16,7 @@
 // Source: solana/runtime/src/append_vec.rs
 
 use {
+    crate::tiered_storage::{is_tiered_storage, is_tiered_storage_file, TieredStorageFile},
     log::*,
     memmap2::{Mmap, MmapMut},
     solana_accounts_db::{
@@ -25,13 +26,15 @@ use {
         u64_align,
     },
     solana_sdk::{
-        account::{Account, AccountSharedData},
+        account::{Account, AccountSharedData, ReadableAccount},
+        clock::Epoch,
         hash::Hash,
+        pubkey::Pubkey,
     },
     std::{
         convert::TryFrom,
         fs::OpenOptions,
-        io::{self, Read},
+        io::{self, Read, Write},
         mem,
         path::Path,
     },
@@ -50,7 +53,7 @@ pub struct StoredAccountMeta<'a> {
     pub hash: &'a Hash,
 }
 
-impl<'a> StoredAccountMeta<'a> {
+impl StoredAccountMeta<'_> {
     /// Return a new Account by copying all the data referenced by the `StoredAccountMeta`.
     pub fn clone_account(&self) -> AccountSharedData {
         AccountSharedData::from(Account {
@@ -63,10 +66,31 @@ impl<'a> StoredAccountMeta<'a> {
     }
 }
 
+impl ReadableAccount for StoredAccountMeta<'_> {
+    fn lamports(&self) -> u64 {
+        self.account_meta.lamports
+    }
+    fn data(&self) -> &[u8] {
+        self.data
+    }
+    fn owner(&self) -> &Pubkey {
+        &self.account_meta.owner
+    }
+    fn executable(&self) -> bool {
+        self.account_meta.executable
+    }
+    fn rent_epoch(&self) -> Epoch {
+        self.account_meta.rent_epoch
+    }
+}
+
 /// A thread-safe, file-backed block of memory used to store `Account` instances. Append operations
 /// are serialized such that only one thread updates the internal `append_lock` at a time. No
 /// restrictions are placed on reading. That is, one may read items from one thread while another
 /// is appending new items.
+///
+/// Tiered storage files, detected by their footer, are read through the same interface, see
+/// [`AppendVec::tiered_storage`].
 pub struct AppendVec {
     /// A file-backed block of memory that is used to store the data for each appended item.
     map: Mmap,
@@ -78,6 +102,11 @@ pub struct AppendVec {
     file_size: u64,
 
     slot: u64,
+
+    id: u64,
+
+    /// The tiered storage file the accounts are read from instead of `map`.
+    tiered_storage: Option<TieredStorageFile>,
 }
 
 impl AppendVec {
@@ -122,10 +151,13 @@ impl AppendVec {
         self.file_size
     }
 
+    /// Opens an AppendVec, or a tiered storage file detected by the footer at its end.
+    /// `current_len` is only used for AppendVecs, tiered storage files are always used in full.
     pub fn new_from_file<P: AsRef<Path>>(
         path: P,
         current_len: usize,
         slot: u64,
+        id: u64,
     ) -> io::Result<Self> {
         let data = OpenOptions::new()
             .read(true)
@@ -134,7 +166,12 @@ impl AppendVec {
             .open(&path)?;
 
         let file_size = std::fs::metadata(&path)?.len();
-        AppendVec::sanitize_len_and_size(current_len, file_size as usize)?;
+        let tiered_storage = if is_tiered_storage_file(&path)? {
+            Some(TieredStorageFile::new_from_file(&path, slot, id)?)
+        } else {
+            AppendVec::sanitize_len_and_size(current_len, file_size as usize)?;
+            None
+        };
 
         let map = unsafe {
             let result = Mmap::map(&data);
@@ -147,9 +184,14 @@ impl AppendVec {
 
         let new = AppendVec {
             map,
-            current_len,
+            current_len: match tiered_storage {
+                Some(_) => file_size as usize,
+                None => current_len,
+            },
             file_size,
             slot,
+            id,
+            tiered_storage,
         };
 
         Ok(new)
@@ -159,17 +201,43 @@ impl AppendVec {
         reader: &mut R,
         current_len: usize,
         slot: u64,
+        id: u64,
     ) -> io::Result<Self> {
         let mut map = MmapMut::map_anon(current_len)?;
         io::copy(&mut reader.take(current_len as u64), &mut map.as_mut())?;
+        Self::new_from_mmap(map.make_read_only()?, current_len, slot, id)
+    }
+
+    /// Uses accounts read into memory. The tiered storage reader maps files only, so a tiered
+    /// storage file is written to a temporary file, which is removed again once mapped.
+    pub fn new_from_mmap(map: Mmap, current_len: usize, slot: u64, id: u64) -> io::Result<Self> {
+        if current_len > map.len() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("current_len is larger than file size ({})", map.len()),
+            ));
+        }
+        if is_tiered_storage(&map[..current_len]) {
+            let mut file = tempfile::NamedTempFile::new()?;
+            file.write_all(&map[..current_len])?;
+            file.flush()?;
+            return Self::new_from_file(file.path(), current_len, slot, id);
+        }
         Ok(AppendVec {
-            map: map.make_read_only()?,
+            file_size: map.len() as u64,
+            map,
             current_len,
-            file_size: current_len as u64,
             slot,
+            id,
+            tiered_storage: None,
         })
     }
 
+    /// The tiered storage file the accounts are read from, if this is not an AppendVec.
+    pub const fn tiered_storage(&self) -> Option<&TieredStorageFile> {
+        self.tiered_storage.as_ref()
+    }
+
     /// Get a reference to the data at `offset` of `size` bytes if that slice
     /// doesn't overrun the internal buffer. Otherwise return None.
     /// Also return the offset of the first byte after the requested data that
@@ -204,7 +272,13 @@ impl AppendVec {
     /// Return account metadata for the account at `offset` if its data doesn't overrun
     /// the internal buffer. Otherwise return None. Also return the offset of the first byte
     /// after the requested data that falls on a 64-byte boundary.
+    ///
+    /// For tiered storage, `offset` is the index of the account and the next offset the index
+    /// of the next account.
     pub fn get_account<'a>(&'a self, offset: usize) -> Option<(StoredAccountMeta<'a>, usize)> {
+        if let Some(tiered_storage) = &self.tiered_storage {
+            return tiered_storage.get_account(offset);
+        }
         let (meta, next): (&'a StoredMeta, _) = self.get_type(offset)?;
         let (account_meta, next): (&'a AccountMeta, _) = self.get_type(next)?;
         let (hash, next): (&'a Hash, _) = self.get_type(next)?;
@@ -226,4 +300,110 @@ impl AppendVec {
     pub const fn slot(&self) -> u64 {
         self.slot
     }
+
+    pub const fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+/// AppendVecs written in the stored layout, for the tests of the modules reading accounts.
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use super::*;
+
+    /// An account to store, with the write version and hash stored with it.
+    #[derive(Clone)]
+    pub(crate) struct TestAccount {
+        pub pubkey: Pubkey,
+        pub write_version: u64,
+        pub hash: Hash,
+        pub account: AccountSharedData,
+    }
+
+    impl TestAccount {
+        pub(crate) fn new(pubkey: Pubkey, account: AccountSharedData) -> Self {
+            Self {
+                pubkey,
+                write_version: 0,
+                hash: Hash::default(),
+                account,
+            }
+        }
+    }
+
+    /// The bytes of an AppendVec storing `accounts` in order.
+    pub(crate) fn append_vec_bytes(accounts: &[TestAccount]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for TestAccount {
+            pubkey,
+            write_version,
+            hash,
+            account,
+        } in accounts
+        {
+            // StoredMeta, AccountMeta and the hash, all aligned to 8 bytes
+            bytes.extend_from_slice(&write_version.to_le_bytes());
+            bytes.extend_from_slice(&(account.data().len() as u64).to_le_bytes());
+            bytes.extend_from_slice(pubkey.as_ref());
+            bytes.extend_from_slice(&account.lamports().to_le_bytes());
+            bytes.extend_from_slice(&account.rent_epoch().to_le_bytes());
+            bytes.extend_from_slice(account.owner().as_ref());
+            bytes.extend_from_slice(&[account.executable() as u8, 0, 0, 0, 0, 0, 0, 0]);
+            bytes.extend_from_slice(hash.as_ref());
+            bytes.extend_from_slice(account.data());
+            bytes.resize(u64_align!(bytes.len()), 0);
+        }
+        bytes
+    }
+
+    /// An AppendVec in memory storing `accounts` in order.
+    pub(crate) fn new_append_vec(slot: u64, id: u64, accounts: &[TestAccount]) -> AppendVec {
+        let bytes = append_vec_bytes(accounts);
+        AppendVec::new_from_reader(&mut bytes.as_slice(), bytes.len(), slot, id).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{test_utils::*, *},
+        crate::append_vec_iter,
+        solana_sdk::account::WritableAccount,
+    };
+
+    #[test]
+    fn reads_stored_accounts() {
+        let owner = Pubkey::new_unique();
+        let mut executable = AccountSharedData::new(1, 3, &owner);
+        executable.set_executable(true);
+        executable.data_as_mut_slice().copy_from_slice(&[1, 2, 3]);
+        let accounts = vec![
+            TestAccount {
+                pubkey: Pubkey::new_unique(),
+                write_version: 7,
+                hash: Hash::new_unique(),
+                account: executable,
+            },
+            TestAccount::new(Pubkey::new_unique(), AccountSharedData::new(0, 0, &owner)),
+        ];
+        let append_vec = new_append_vec(42, 3, &accounts);
+        assert!(append_vec.tiered_storage().is_none());
+        assert_eq!((append_vec.slot(), append_vec.id()), (42, 3));
+
+        let stored: Vec<_> = append_vec_iter(&append_vec)
+            .map(|handle| handle.access().unwrap())
+            .collect();
+        assert_eq!(stored.len(), 2);
+        for (stored, expected) in stored.iter().zip(&accounts) {
+            assert_eq!(stored.meta.pubkey, expected.pubkey);
+            assert_eq!(stored.meta.write_version_obsolete, expected.write_version);
+            assert_eq!(*stored.hash, expected.hash);
+            assert_eq!(stored.clone_account(), expected.account);
+        }
+        // 48 + 56 + 32 bytes of metas and 3 bytes of data, aligned
+        assert_eq!((stored[0].offset, stored[0].stored_size), (0, 144));
+        assert_eq!(stored[1].offset, 144);
+        assert_eq!(append_vec.len(), 144 + 136);
+        assert!(append_vec.get_account(append_vec.len()).is_none());
+    }
 }
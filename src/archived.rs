@@ -1,28 +1,44 @@
 use {
     crate::{
+        decoder::{archive_format_from_path, ArchiveDecoder},
         deserialize_from, parse_append_vec_name, AccountsDbFields, AppendVec, AppendVecIterator,
-        DeserializableVersionedBank, SerializableAccountStorageEntry, SnapshotError,
-        SnapshotExtractor, SnapshotResult,
+        DeserializableVersionedBank, ExtraFieldsToDeserialize, SerializableAccountStorageEntry,
+        SnapshotError, SnapshotExtractor, SnapshotResult,
     },
     log::info,
+    solana_runtime::snapshot_utils::ArchiveFormat,
     std::{
         fs::File,
         io::{BufReader, Read},
-        path::{Component, Path},
+        path::{Component, Path, PathBuf},
         pin::Pin,
         time::Instant,
     },
     tar::{Archive, Entries, Entry},
+    tempfile::TempDir,
 };
 
-/// Extracts account data from a .tar.zst stream.
+/// An AppendVec that appeared in the archive before the snapshot manifest.
+/// Its contents were spilled to disk until the manifest could be read.
+struct SpilledAppendVec {
+    slot: u64,
+    id: u64,
+    path: PathBuf,
+    current_len: usize,
+}
+
+/// Extracts account data from a snapshot archive stream, e.g. .tar.zst or .tar.bz2.
 pub struct ArchiveSnapshotExtractor<Source>
 where
     Source: Read + Unpin + 'static,
 {
+    bank_fields: DeserializableVersionedBank,
     accounts_db_fields: AccountsDbFields<SerializableAccountStorageEntry>,
-    _archive: Pin<Box<Archive<zstd::Decoder<'static, BufReader<Source>>>>>,
-    entries: Option<Entries<'static, zstd::Decoder<'static, BufReader<Source>>>>,
+    extra_fields: ExtraFieldsToDeserialize,
+    _archive: Pin<Box<Archive<ArchiveDecoder<Source>>>>,
+    entries: Option<Entries<'static, ArchiveDecoder<Source>>>,
+    spilled_append_vecs: Vec<SpilledAppendVec>,
+    _spill_dir: Option<TempDir>,
 }
 
 impl<Source> SnapshotExtractor for ArchiveSnapshotExtractor<Source>
@@ -32,14 +48,37 @@ where
     fn iter(&mut self) -> AppendVecIterator<'_> {
         Box::new(self.unboxed_iter())
     }
+
+    fn bank_fields(&self) -> &DeserializableVersionedBank {
+        &self.bank_fields
+    }
+
+    fn accounts_db_fields(&self) -> &AccountsDbFields<SerializableAccountStorageEntry> {
+        &self.accounts_db_fields
+    }
+
+    fn extra_fields(&self) -> &ExtraFieldsToDeserialize {
+        &self.extra_fields
+    }
 }
 
 impl<Source> ArchiveSnapshotExtractor<Source>
 where
     Source: Read + Unpin + 'static,
 {
+    /// Reads an archive, detecting its compression from the first bytes of the stream.
     pub fn from_reader(source: Source) -> SnapshotResult<Self> {
-        let tar_stream = zstd::stream::read::Decoder::new(source)?;
+        Self::from_decoder(ArchiveDecoder::detect(source)?)
+    }
+
+    pub fn from_reader_with_format(
+        source: Source,
+        archive_format: ArchiveFormat,
+    ) -> SnapshotResult<Self> {
+        Self::from_decoder(ArchiveDecoder::new(source, archive_format)?)
+    }
+
+    pub fn from_decoder(tar_stream: ArchiveDecoder<Source>) -> SnapshotResult<Self> {
         let mut archive = Box::pin(Archive::new(tar_stream));
 
         // This is safe as long as we guarantee that entries never gets accessed past drop.
@@ -47,16 +86,35 @@ where
         let mut entries = archive_static.entries()?;
 
         // Search for snapshot manifest.
+        // AppendVecs preceding it are spilled to a temporary directory, because their
+        // lengths are only known once the manifest has been read.
         let mut snapshot_file: Option<Entry<_>> = None;
+        let mut spill_dir: Option<TempDir> = None;
+        let mut spilled: Vec<(u64, u64, PathBuf)> = Vec::new();
         for entry in entries.by_ref() {
-            let entry = entry?;
+            let mut entry = entry?;
             let path = entry.path()?;
             if Self::is_snapshot_manifest_file(&path) {
                 snapshot_file = Some(entry);
                 break;
             } else if Self::is_appendvec_file(&path) {
-                // TODO Support archives where AppendVecs precede snapshot manifests
-                return Err(SnapshotError::UnexpectedAppendVec);
+                let name = path
+                    .file_name()
+                    .ok_or(SnapshotError::UnexpectedAppendVec)?
+                    .to_owned();
+                let (slot, id) =
+                    parse_append_vec_name(&name).ok_or(SnapshotError::UnexpectedAppendVec)?;
+                let dir = match spill_dir.as_ref() {
+                    Some(dir) => dir,
+                    None => spill_dir.insert(
+                        tempfile::Builder::new()
+                            .prefix("solana-accountsdb-reader-")
+                            .tempdir()?,
+                    ),
+                };
+                let spill_path = dir.path().join(name);
+                entry.unpack(&spill_path)?;
+                spilled.push((slot, id, spill_path));
             }
         }
         let snapshot_file = snapshot_file.ok_or(SnapshotError::NoSnapshotManifest)?;
@@ -67,13 +125,17 @@ where
         let mut snapshot_file = BufReader::new(snapshot_file);
 
         let pre_unpack = Instant::now();
-        let versioned_bank: DeserializableVersionedBank = deserialize_from(&mut snapshot_file)?;
-        drop(versioned_bank);
+        let mut bank_fields: DeserializableVersionedBank = deserialize_from(&mut snapshot_file)?;
         let versioned_bank_post_time = Instant::now();
 
         let accounts_db_fields: AccountsDbFields<SerializableAccountStorageEntry> =
             deserialize_from(&mut snapshot_file)?;
         let accounts_db_fields_post_time = Instant::now();
+
+        let extra_fields: ExtraFieldsToDeserialize = deserialize_from(&mut snapshot_file)?;
+        bank_fields.fee_rate_governor = bank_fields
+            .fee_rate_governor
+            .clone_with_lamports_per_signature(extra_fields.lamports_per_signature);
         drop(snapshot_file);
 
         info!(
@@ -85,15 +147,55 @@ where
             accounts_db_fields_post_time - versioned_bank_post_time
         );
 
+        if let Some(dir) = spill_dir.as_ref() {
+            info!(
+                "Spilled {} AppendVecs preceding the snapshot manifest to {:?}",
+                spilled.len(),
+                dir.path()
+            );
+        }
+        let spilled_append_vecs = spilled
+            .into_iter()
+            .map(|(slot, id, path)| {
+                Ok(SpilledAppendVec {
+                    slot,
+                    id,
+                    path,
+                    current_len: Self::known_append_vec_len(&accounts_db_fields, slot, id)?,
+                })
+            })
+            .collect::<SnapshotResult<Vec<_>>>()?;
+
         Ok(ArchiveSnapshotExtractor {
             _archive: archive,
+            bank_fields,
             accounts_db_fields,
+            extra_fields,
             entries: Some(entries),
+            spilled_append_vecs,
+            _spill_dir: spill_dir,
         })
     }
 
+    /// Slot of the bank the snapshot was taken at.
+    pub const fn slot(&self) -> u64 {
+        self.accounts_db_fields.slot()
+    }
+
     fn unboxed_iter(&mut self) -> impl Iterator<Item = SnapshotResult<AppendVec>> + '_ {
-        self.entries
+        let spilled = std::mem::take(&mut self.spilled_append_vecs)
+            .into_iter()
+            .map(|spilled| {
+                info!("Reading spilled AppendVec {}.{}", spilled.slot, spilled.id);
+                Ok(AppendVec::new_from_file(
+                    &spilled.path,
+                    spilled.current_len,
+                    spilled.slot,
+                    spilled.id,
+                )?)
+            });
+        let streamed = self
+            .entries
             .take()
             .into_iter()
             .flatten()
@@ -108,31 +210,36 @@ where
                 };
                 let (slot, id) = path.file_name().and_then(parse_append_vec_name)?;
                 Some(self.process_entry(&mut entry, slot, id))
-            })
+            });
+        spilled.chain(streamed)
     }
 
     fn process_entry(
         &self,
-        entry: &mut Entry<'static, zstd::Decoder<'static, BufReader<Source>>>,
+        entry: &mut Entry<'static, ArchiveDecoder<Source>>,
         slot: u64,
         id: u64,
     ) -> SnapshotResult<AppendVec> {
-        let known_vecs = self
-            .accounts_db_fields
+        let current_len = Self::known_append_vec_len(&self.accounts_db_fields, slot, id)?;
+        Ok(AppendVec::new_from_reader(entry, current_len, slot, id)?)
+    }
+
+    /// Looks up the length of an AppendVec in the snapshot manifest.
+    fn known_append_vec_len(
+        accounts_db_fields: &AccountsDbFields<SerializableAccountStorageEntry>,
+        slot: u64,
+        id: u64,
+    ) -> SnapshotResult<usize> {
+        let known_vecs = accounts_db_fields
             .0
             .get(&slot)
             .map(|v| &v[..])
             .unwrap_or(&[]);
-        let known_vec = known_vecs.iter().find(|entry| entry.id == (id as usize));
-        let known_vec = match known_vec {
-            None => return Err(SnapshotError::UnexpectedAppendVec),
-            Some(v) => v,
-        };
-        Ok(AppendVec::new_from_reader(
-            entry,
-            known_vec.accounts_current_len,
-            slot,
-        )?)
+        known_vecs
+            .iter()
+            .find(|entry| entry.id == (id as usize))
+            .map(|known_vec| known_vec.accounts_current_len)
+            .ok_or(SnapshotError::UnexpectedAppendVec)
     }
 
     fn is_snapshot_manifest_file(path: &Path) -> bool {
@@ -173,7 +280,211 @@ where
 }
 
 impl ArchiveSnapshotExtractor<File> {
+    /// Opens an archive, picking its compression from the file extension if it has a known one.
     pub fn open(path: &Path) -> SnapshotResult<Self> {
-        Self::from_reader(File::open(path)?)
+        let file = File::open(path)?;
+        match archive_format_from_path(path) {
+            Some(archive_format) => Self::from_reader_with_format(file, archive_format),
+            None => Self::from_reader(file),
+        }
+    }
+}
+
+/// Snapshot archives of banks, for the tests of the modules reading snapshots.
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use {
+        super::*,
+        solana_runtime::{
+            bank::Bank, genesis_utils::create_genesis_config,
+            snapshot_archive_info::SnapshotArchiveInfoGetter, snapshot_bank_utils,
+        },
+        solana_sdk::pubkey::Pubkey,
+        std::{fs, sync::Arc},
+    };
+
+    /// A bank at slot 0 of a new cluster.
+    pub(crate) fn new_bank() -> Bank {
+        let genesis = create_genesis_config(1_000_000_000_000);
+        Bank::new_for_tests(&genesis.genesis_config)
+    }
+
+    /// Completes `parent` and starts a bank at `slot` on top of it.
+    pub(crate) fn new_child_bank(parent: Bank, slot: u64) -> Bank {
+        parent.fill_bank_with_ticks_for_tests();
+        Bank::new_from_parent(Arc::new(parent), &Pubkey::default(), slot)
+    }
+
+    fn complete(bank: &Bank, dir: &Path) {
+        bank.set_capitalization();
+        if !bank.is_complete() {
+            bank.fill_bank_with_ticks_for_tests();
+        }
+        for directory in ["bank", "full", "incremental"] {
+            fs::create_dir_all(dir.join(directory)).unwrap();
+        }
+    }
+
+    /// Completes `bank` and writes its full snapshot archive to `dir`, returning its path.
+    pub(crate) fn write_full_snapshot_archive(
+        bank: &Bank,
+        dir: &Path,
+        archive_format: ArchiveFormat,
+    ) -> PathBuf {
+        complete(bank, dir);
+        snapshot_bank_utils::bank_to_full_snapshot_archive(
+            dir.join("bank"),
+            bank,
+            None,
+            dir.join("full"),
+            dir.join("incremental"),
+            archive_format,
+        )
+        .unwrap()
+        .path()
+        .clone()
+    }
+
+    /// Completes `bank` and writes its incremental snapshot archive on top of the full snapshot
+    /// at `full_slot` to `dir`, returning its path.
+    pub(crate) fn write_incremental_snapshot_archive(
+        bank: &Bank,
+        full_slot: u64,
+        dir: &Path,
+        archive_format: ArchiveFormat,
+    ) -> PathBuf {
+        complete(bank, dir);
+        snapshot_bank_utils::bank_to_incremental_snapshot_archive(
+            dir.join("bank"),
+            bank,
+            full_slot,
+            None,
+            dir.join("full"),
+            dir.join("incremental"),
+            archive_format,
+        )
+        .unwrap()
+        .path()
+        .clone()
+    }
+
+    /// Unpacks the snapshot archive at `archive` into `dir`, where it can be opened as an
+    /// unpacked snapshot.
+    pub(crate) fn unpack_archive(archive: &Path, dir: &Path) {
+        let archive_format = archive_format_from_path(archive).unwrap();
+        let decoder = ArchiveDecoder::new(File::open(archive).unwrap(), archive_format).unwrap();
+        Archive::new(decoder).unpack(dir).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{test_utils::*, *},
+        crate::append_vec_iter,
+        solana_sdk::{account::AccountSharedData, pubkey::Pubkey},
+        tar::{Builder, Header},
+    };
+
+    /// Pubkeys of the stored accounts of each accounts file, by slot and id.
+    fn stored_pubkeys(extractor: &mut impl SnapshotExtractor) -> Vec<((u64, u64), Vec<Pubkey>)> {
+        let mut files: Vec<_> = extractor
+            .iter()
+            .map(|append_vec| {
+                let append_vec = append_vec.unwrap();
+                let pubkeys = append_vec_iter(&append_vec)
+                    .map(|handle| handle.access().unwrap().meta.pubkey)
+                    .collect();
+                ((append_vec.slot(), append_vec.id()), pubkeys)
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn spills_append_vecs_preceding_the_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let bank = new_bank();
+        let owner = Pubkey::new_unique();
+        for lamports in 1..10 {
+            bank.store_account(
+                &Pubkey::new_unique(),
+                &AccountSharedData::new(lamports, 0, &owner),
+            );
+        }
+        let archive = write_full_snapshot_archive(&bank, dir.path(), ArchiveFormat::Tar);
+
+        // the same entries with the accounts files first
+        let mut entries = Vec::new();
+        for entry in Archive::new(File::open(&archive).unwrap())
+            .entries()
+            .unwrap()
+        {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().into_owned();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).unwrap();
+            entries.push((entry.header().clone(), path, data));
+        }
+        entries.sort_by_key(|(_, path, _)| !path.starts_with("accounts"));
+        let reordered = dir.path().join("reordered.tar");
+        let mut builder = Builder::new(File::create(&reordered).unwrap());
+        for (mut header, path, data) in entries {
+            builder
+                .append_data(&mut header, path, data.as_slice())
+                .unwrap();
+        }
+        builder.into_inner().unwrap();
+        let first_path = Archive::new(File::open(&reordered).unwrap())
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().into_owned())
+            .find(|path| path.starts_with("accounts") || path.starts_with("snapshots"))
+            .unwrap();
+        assert!(ArchiveSnapshotExtractor::<File>::is_appendvec_file(
+            &first_path
+        ));
+
+        let expected = stored_pubkeys(&mut ArchiveSnapshotExtractor::open(&archive).unwrap());
+        let mut extractor = ArchiveSnapshotExtractor::open(&reordered).unwrap();
+        assert_eq!(extractor.slot(), bank.slot());
+        assert!(!extractor.spilled_append_vecs.is_empty());
+        let spilled = stored_pubkeys(&mut extractor);
+        assert!(spilled.iter().any(|(_, pubkeys)| pubkeys.len() >= 9));
+        assert_eq!(spilled, expected);
+    }
+
+    #[test]
+    fn rejects_append_vecs_missing_from_the_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = write_full_snapshot_archive(&new_bank(), dir.path(), ArchiveFormat::Tar);
+
+        // an accounts file the manifest does not know, ahead of the manifest
+        let extended = dir.path().join("extended.tar");
+        let mut builder = Builder::new(File::create(&extended).unwrap());
+        let mut header = Header::new_gnu();
+        header.set_size(0);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "accounts/12345.678", [].as_slice())
+            .unwrap();
+        let mut original = Archive::new(File::open(&archive).unwrap());
+        for entry in original.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let mut header = entry.header().clone();
+            let path = entry.path().unwrap().into_owned();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).unwrap();
+            builder
+                .append_data(&mut header, path, data.as_slice())
+                .unwrap();
+        }
+        builder.into_inner().unwrap();
+
+        assert!(matches!(
+            ArchiveSnapshotExtractor::open(&extended).err().unwrap(),
+            SnapshotError::UnexpectedAppendVec
+        ));
     }
 }
@@ -1,36 +1,165 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
-use std::str::FromStr;
-use log::warn;
 use {
-    log::info,
+    log::{info, warn},
     reqwest::blocking::Response,
     solana_accountsdb_reader::{
         append_vec::AppendVec,
+        anchor::{AnchorIdl, AnchorIdls},
+        accounts_hash::{archive_snapshot_hash, AccountsHashVerification},
         append_vec_iter,
         archived::ArchiveSnapshotExtractor,
-        parallel::{par_iter_append_vecs, AppendVecConsumer},
+        config::{validator_infos, ValidatorInfo},
+        geyser::{self, LoadedGeyserPlugin},
+        holders::{aggregate_by_owner, token_holders},
+        incremental::IncrementalSnapshotExtractor,
+        indexed::IndexedAccounts,
+        lookup_table::lookup_tables,
+        parallel::AppendVecConsumer,
+        programs::{deployed_programs, write_programs},
+        proof::{AccountProof, AccountsMerkleTree},
+        rpc::{self, RpcHandler},
+        secondary_index::SecondaryIndex,
+        stake::{
+            compare_with_bank, decode_vote_account, delegation_report, new_rate_activation_epoch,
+        },
+        solana::{
+            AccountsDbFields, DeserializableVersionedBank, ExtraFieldsToDeserialize,
+            SerializableAccountStorageEntry,
+        },
+        latest::{LatestAccounts, LatestPositions},
+        lt_hash::AccountsLtHashVerification,
         unpacked::UnpackedSnapshotExtractor,
-        AppendVecIterator, ReadProgressTracking, SnapshotError, SnapshotExtractor, SnapshotResult,
+        verify::verify_account_hashes,
+        AppendVecIterator, NoopReadProgressTracking, ReadProgressTracking, SnapshotExtractor,
     },
     std::{
         fs::File,
-        io::{IoSliceMut, Read},
-        path::Path,
+        io::{self, BufWriter, Write},
+        net::SocketAddr,
+        path::{Path, PathBuf},
         sync::Arc,
     },
 };
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use solana_accounts_db::accounts_index::AccountIndex;
 use itertools::Itertools;
 use solana_sdk::clock::Slot;
+use solana_sdk::hash::Hash;
 use solana_sdk::pubkey::Pubkey;
 
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
+    /// snapshot archive, unpacked snapshot directory or URL; required by all commands reading a snapshot
     #[arg(long)]
-    pub snapshot_archive_path: String,
+    pub snapshot_archive_path: Option<String>,
+    /// incremental snapshot archive based on the full snapshot archive
+    #[arg(long)]
+    pub incremental_snapshot_archive_path: Option<String>,
+    /// prints per-slot account statistics when omitted
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Check the stored account hashes and recompute the accounts hash and accounts lt hash
+    Verify,
+    /// Produce a Merkle inclusion proof of an account in the accounts hash of a full snapshot
+    Prove {
+        #[arg(long)]
+        pubkey: Pubkey,
+        /// file to write the JSON proof to instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Check a JSON inclusion proof against an accounts hash, without reading a snapshot
+    VerifyProof {
+        #[arg(long)]
+        proof: PathBuf,
+        #[arg(long)]
+        accounts_hash: Hash,
+    },
+    /// Export all holders of a token mint as CSV, sorted by balance
+    Holders {
+        #[arg(long)]
+        mint: Pubkey,
+        /// sum the balances of all token accounts of an owner
+        #[arg(long)]
+        aggregate: bool,
+        /// leave out token accounts with a zero balance
+        #[arg(long)]
+        skip_empty: bool,
+        /// file to write the CSV to instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Print the sysvars and check Clock and EpochSchedule against the snapshot manifest
+    Sysvars,
+    /// Write an index of the position of the latest copy of every account
+    BuildIndex {
+        #[arg(long)]
+        output: PathBuf,
+        /// also write a secondary index next to it: program-id, spl-token-mint or
+        /// spl-token-owner, can be given multiple times
+        #[arg(long, value_parser = parse_account_index)]
+        account_index: Vec<AccountIndex>,
+    },
+    /// Write the ELF of every deployed program to <program_id>.so, with a manifest.json
+    ExtractPrograms {
+        #[arg(long)]
+        output_dir: PathBuf,
+    },
+    /// Dump every address lookup table as JSON lines
+    LookupTables {
+        /// file to write the JSON lines to instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Decode the accounts of Anchor programs with their IDLs as JSON lines
+    DecodeAnchor {
+        /// Anchor IDL JSON file, can be given multiple times
+        #[arg(long, required = true)]
+        idl: Vec<PathBuf>,
+        /// program the IDL belongs to, for a single IDL that does not contain its address
+        #[arg(long)]
+        program_id: Option<Pubkey>,
+        /// file to write the JSON lines to instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Join the published validator info with the vote accounts as JSON lines
+    ValidatorInfo {
+        /// file to write the JSON lines to instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Report the stake delegated to each vote account as CSV and check it against the bank
+    Delegations {
+        /// file to write the CSV to instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Answer account queries of the Solana JSON-RPC API from an unpacked snapshot
+    Serve {
+        /// index of the snapshot, built first if the file does not exist
+        #[arg(long)]
+        index: PathBuf,
+        /// secondary index to use, built first if its file does not exist: program-id,
+        /// spl-token-mint or spl-token-owner, can be given multiple times; getProgramAccounts
+        /// needs program-id and getTokenAccountsByOwner spl-token-owner
+        #[arg(long, value_parser = parse_account_index)]
+        account_index: Vec<AccountIndex>,
+        #[arg(long, default_value = "127.0.0.1:8899")]
+        bind: SocketAddr,
+    },
+    /// Send the latest accounts to a Geyser plugin like a validator does at startup
+    GeyserReplay {
+        /// plugin config JSON with the libpath of the plugin library, as for --geyser-plugin-config
+        #[arg(long)]
+        config: PathBuf,
+    },
 }
 
 #[tokio::main]
@@ -39,12 +168,503 @@ async fn main() -> anyhow::Result<()> {
         env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info"),
     );
 
-    let Args { snapshot_archive_path } = Args::parse();
+    let Args {
+        snapshot_archive_path,
+        incremental_snapshot_archive_path,
+        command,
+    } = Args::parse();
+
+    if let Some(Command::VerifyProof {
+        proof,
+        accounts_hash,
+    }) = &command
+    {
+        return verify_proof(proof, accounts_hash);
+    }
+
+    let snapshot_archive_path = snapshot_archive_path
+        .ok_or_else(|| anyhow::anyhow!("--snapshot-archive-path is required"))?;
+    let open_loader = || match &incremental_snapshot_archive_path {
+        Some(incremental_snapshot_archive_path) => SupportedLoader::new_incremental(
+            snapshot_archive_path.as_ref(),
+            incremental_snapshot_archive_path.as_ref(),
+        ),
+        None => SupportedLoader::new(&snapshot_archive_path, Box::new(NoopReadProgressTracking {})),
+    };
+    let mut loader = open_loader()?;
+
+    let bank_fields = loader.bank_fields();
+    info!(
+        "Snapshot bank at slot {} (epoch {}), capitalization {} lamports",
+        bank_fields.slot, bank_fields.epoch, bank_fields.capitalization
+    );
+    if let Some(epoch_accounts_hash) = loader.extra_fields().epoch_accounts_hash {
+        info!("Epoch accounts hash: {}", epoch_accounts_hash);
+    }
+
+    match command {
+        None => print_stats(&mut loader),
+        Some(Command::Verify) => verify(&mut loader, Path::new(&snapshot_archive_path)),
+        Some(Command::Prove { pubkey, output }) => prove(&mut loader, &pubkey, output.as_deref()),
+        Some(Command::Holders {
+            mint,
+            aggregate,
+            skip_empty,
+            output,
+        }) => holders(&mut loader, &mint, aggregate, skip_empty, output.as_deref()),
+        Some(Command::Sysvars) => sysvars(&mut loader),
+        Some(Command::BuildIndex {
+            output,
+            account_index,
+        }) => {
+            // an archive can be read only once, so both are built in the same pass
+            let (index, _) = SecondaryIndex::build_with_index(&mut loader, &output, &account_index)?;
+            info!("Indexed {} accounts at slot {}", index.len(), index.slot());
+            Ok(())
+        }
+        Some(Command::ExtractPrograms { output_dir }) => extract_programs(&mut loader, &output_dir),
+        Some(Command::LookupTables { output }) => dump_lookup_tables(&mut loader, output.as_deref()),
+        Some(Command::DecodeAnchor {
+            idl,
+            program_id,
+            output,
+        }) => decode_anchor(&mut loader, &idl, program_id, output.as_deref()),
+        Some(Command::ValidatorInfo { output }) => validator_info(&mut loader, output.as_deref()),
+        Some(Command::Delegations { output }) => delegations(&mut loader, output.as_deref()),
+        Some(Command::Serve {
+            index,
+            account_index,
+            bind,
+        }) => serve(loader, &index, &account_index, bind).await,
+        Some(Command::GeyserReplay { config }) => {
+            // SAFETY: the plugin library is trusted like a validator trusts its plugins
+            let mut plugin = unsafe { LoadedGeyserPlugin::load(&config)? };
+            let positions = LatestPositions::resolve(&mut loader)?;
+            // an archive can be read only once, so the accounts are streamed from a new reader
+            let mut loader = open_loader()?;
+            geyser::replay_snapshot(plugin.plugin_mut(), &positions, &mut loader)?;
+            Ok(())
+        }
+        Some(Command::VerifyProof { .. }) => unreachable!("handled without a snapshot"),
+    }
+}
+
+/// Accepts the names of the validator's `--account-index` option.
+fn parse_account_index(name: &str) -> Result<AccountIndex, String> {
+    match name {
+        "program-id" => Ok(AccountIndex::ProgramId),
+        "spl-token-mint" => Ok(AccountIndex::SplTokenMint),
+        "spl-token-owner" => Ok(AccountIndex::SplTokenOwner),
+        _ => Err(format!(
+            "unknown account index {}, expected program-id, spl-token-mint or spl-token-owner",
+            name
+        )),
+    }
+}
+
+fn prove(loader: &mut SupportedLoader, pubkey: &Pubkey, output: Option<&Path>) -> anyhow::Result<()> {
+    let accounts = LatestAccounts::from_extractor(loader)?;
+    let tree = AccountsMerkleTree::new(&accounts);
+    info!("Accounts hash: {}", tree.root());
+
+    let proof = tree
+        .prove(pubkey)
+        .ok_or_else(|| anyhow::anyhow!("account {} not found in snapshot", pubkey))?;
+    match output {
+        Some(output) => serde_json::to_writer_pretty(File::create(output)?, &proof)?,
+        None => println!("{}", serde_json::to_string_pretty(&proof)?),
+    }
+    Ok(())
+}
+
+fn holders(
+    loader: &mut SupportedLoader,
+    mint: &Pubkey,
+    aggregate: bool,
+    skip_empty: bool,
+    output: Option<&Path>,
+) -> anyhow::Result<()> {
+    let accounts = LatestAccounts::from_extractor(loader)?;
+    let holders = token_holders(&accounts, mint, skip_empty);
+
+    let mut writer: BufWriter<Box<dyn Write>> = BufWriter::new(match output {
+        Some(output) => Box::new(File::create(output)?),
+        None => Box::new(io::stdout().lock()),
+    });
+    if aggregate {
+        writeln!(writer, "owner,amount,token_accounts")?;
+        for balance in aggregate_by_owner(&holders) {
+            writeln!(
+                writer,
+                "{},{},{}",
+                balance.owner, balance.amount, balance.token_accounts
+            )?;
+        }
+    } else {
+        writeln!(writer, "owner,token_account,amount,delegate,delegated_amount")?;
+        for holder in &holders {
+            writeln!(
+                writer,
+                "{},{},{},{},{}",
+                holder.owner,
+                holder.token_account,
+                holder.amount,
+                holder.delegate.map(|delegate| delegate.to_string()).unwrap_or_default(),
+                holder.delegated_amount
+            )?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn extract_programs(loader: &mut SupportedLoader, output_dir: &Path) -> anyhow::Result<()> {
+    let accounts = LatestAccounts::from_extractor(loader)?;
+    let programs = deployed_programs(&accounts);
+
+    write_programs(&programs, output_dir)?;
+    info!(
+        "Extracted {} programs to {}",
+        programs.len(),
+        output_dir.display()
+    );
+    Ok(())
+}
+
+fn dump_lookup_tables(loader: &mut SupportedLoader, output: Option<&Path>) -> anyhow::Result<()> {
+    let accounts = LatestAccounts::from_extractor(loader)?;
+
+    let mut writer: BufWriter<Box<dyn Write>> = BufWriter::new(match output {
+        Some(output) => Box::new(File::create(output)?),
+        None => Box::new(io::stdout().lock()),
+    });
+    let mut count = 0;
+    for (address, lookup_table) in lookup_tables(&accounts) {
+        let meta = &lookup_table.meta;
+        serde_json::to_writer(
+            &mut writer,
+            &serde_json::json!({
+                "address": address.to_string(),
+                "authority": meta.authority.map(|authority| authority.to_string()),
+                "deactivation_slot": meta.deactivation_slot,
+                "last_extended_slot": meta.last_extended_slot,
+                "last_extended_slot_start_index": meta.last_extended_slot_start_index,
+                "addresses": lookup_table
+                    .addresses
+                    .iter()
+                    .map(|address| address.to_string())
+                    .collect::<Vec<_>>(),
+            }),
+        )?;
+        writeln!(writer)?;
+        count += 1;
+    }
+    writer.flush()?;
+    info!("Dumped {} address lookup tables", count);
+    Ok(())
+}
+
+fn decode_anchor(
+    loader: &mut SupportedLoader,
+    idl_paths: &[PathBuf],
+    program_id: Option<Pubkey>,
+    output: Option<&Path>,
+) -> anyhow::Result<()> {
+    if program_id.is_some() && idl_paths.len() > 1 {
+        anyhow::bail!("--program-id can only be used with a single --idl");
+    }
+    let mut idls = AnchorIdls::new();
+    for idl_path in idl_paths {
+        let idl = AnchorIdl::from_file(idl_path)?;
+        match program_id {
+            Some(program_id) => idls.insert(program_id, idl),
+            None => idls.insert_with_address(idl).map_err(|err| {
+                anyhow::anyhow!("{}: {}, use --program-id", idl_path.display(), err)
+            })?,
+        }
+    }
 
-    let archive_path = PathBuf::from_str(snapshot_archive_path.as_str()).unwrap();
+    let accounts = LatestAccounts::from_extractor(loader)?;
+    let mut writer: BufWriter<Box<dyn Write>> = BufWriter::new(match output {
+        Some(output) => Box::new(File::create(output)?),
+        None => Box::new(io::stdout().lock()),
+    });
+    let (mut decoded, mut failed) = (0, 0);
+    for (stored, account) in idls.decode_accounts(&accounts) {
+        match account {
+            Ok(account) => {
+                serde_json::to_writer(
+                    &mut writer,
+                    &serde_json::json!({
+                        "pubkey": stored.meta.pubkey.to_string(),
+                        "owner": stored.account_meta.owner.to_string(),
+                        "account_type": account.account_type,
+                        "data": account.data,
+                    }),
+                )?;
+                writeln!(writer)?;
+                decoded += 1;
+            }
+            Err(err) => {
+                warn!("Failed to decode account {}: {}", stored.meta.pubkey, err);
+                failed += 1;
+            }
+        }
+    }
+    writer.flush()?;
+    info!("Decoded {} accounts, {} failed", decoded, failed);
+    Ok(())
+}
 
-    let mut loader: ArchiveSnapshotExtractor<File> = ArchiveSnapshotExtractor::open(&archive_path).unwrap();
+fn validator_info(loader: &mut SupportedLoader, output: Option<&Path>) -> anyhow::Result<()> {
+    let accounts = LatestAccounts::from_extractor(loader)?;
+    let mut infos = validator_infos(&accounts);
+    let mut vote_accounts: Vec<_> = accounts
+        .iter()
+        .filter_map(|(_, handle)| {
+            let stored = handle.access()?;
+            Some((stored.meta.pubkey, decode_vote_account(&stored)?))
+        })
+        .collect();
+    vote_accounts.sort_unstable_by_key(|(vote_account, vote_state)| (vote_state.node_pubkey, *vote_account));
 
+    let mut writer: BufWriter<Box<dyn Write>> = BufWriter::new(match output {
+        Some(output) => Box::new(File::create(output)?),
+        None => Box::new(io::stdout().lock()),
+    });
+    let mut write_line = |identity: &Pubkey,
+                          vote_account: Option<&Pubkey>,
+                          commission: Option<u8>,
+                          info: Option<&ValidatorInfo>|
+     -> anyhow::Result<()> {
+        serde_json::to_writer(
+            &mut writer,
+            &serde_json::json!({
+                "identity": identity.to_string(),
+                "vote_account": vote_account.map(|vote_account| vote_account.to_string()),
+                "commission": commission,
+                "name": info.and_then(|info| info.name.as_deref()),
+                "website": info.and_then(|info| info.website.as_deref()),
+                "details": info.and_then(|info| info.details.as_deref()),
+                "keybase_username": info.and_then(|info| info.keybase_username.as_deref()),
+                "icon_url": info.and_then(|info| info.icon_url.as_deref()),
+            }),
+        )?;
+        writeln!(writer)?;
+        Ok(())
+    };
+    let mut with_info = 0;
+    for (vote_account, vote_state) in &vote_accounts {
+        let info = infos.get(&vote_state.node_pubkey);
+        with_info += usize::from(info.is_some());
+        write_line(
+            &vote_state.node_pubkey,
+            Some(vote_account),
+            Some(vote_state.commission),
+            info,
+        )?;
+    }
+    // validators that published info but have no vote account
+    for (_, vote_state) in &vote_accounts {
+        infos.remove(&vote_state.node_pubkey);
+    }
+    let infos: Vec<ValidatorInfo> = infos.into_values().collect();
+    for info in &infos {
+        write_line(&info.identity, None, None, Some(info))?;
+    }
+    writer.flush()?;
+    info!(
+        "Found {} vote accounts, {} with validator info, and {} validator infos without vote account",
+        vote_accounts.len(),
+        with_info,
+        infos.len()
+    );
+    Ok(())
+}
+
+fn sysvars(loader: &mut SupportedLoader) -> anyhow::Result<()> {
+    let sysvars = loader.sysvars()?;
+    if let Some(clock) = &sysvars.clock {
+        info!("{:?}", clock);
+    }
+    if let Some(epoch_schedule) = &sysvars.epoch_schedule {
+        info!("{:?}", epoch_schedule);
+    }
+    if let Some(rent) = &sysvars.rent {
+        info!("{:?}", rent);
+    }
+    if let Some(epoch_rewards) = &sysvars.epoch_rewards {
+        info!("{:?}", epoch_rewards);
+    }
+    if let Some(last_restart_slot) = &sysvars.last_restart_slot {
+        info!("{:?}", last_restart_slot);
+    }
+    if let Some(slot_hashes) = &sysvars.slot_hashes {
+        info!(
+            "SlotHashes with {} entries, newest {:?}",
+            slot_hashes.len(),
+            slot_hashes.first()
+        );
+    }
+    if let Some(slot_history) = &sysvars.slot_history {
+        info!("SlotHistory up to slot {}", slot_history.newest());
+    }
+    if let Some(stake_history) = &sysvars.stake_history {
+        info!(
+            "StakeHistory with {} entries, newest {:?}",
+            stake_history.len(),
+            stake_history.first()
+        );
+    }
+
+    let mismatches = sysvars.verify(loader.bank_fields());
+    for mismatch in &mismatches {
+        warn!("Sysvar mismatch: {}", mismatch);
+    }
+    if !mismatches.is_empty() {
+        anyhow::bail!("{} sysvars do not match the snapshot manifest", mismatches.len());
+    }
+    info!("Sysvars match the snapshot manifest");
+    Ok(())
+}
+
+fn delegations(loader: &mut SupportedLoader, output: Option<&Path>) -> anyhow::Result<()> {
+    let accounts = LatestAccounts::from_extractor(loader)?;
+    let bank_fields = loader.bank_fields();
+    let stakes = &bank_fields.stakes;
+    let validators = delegation_report(
+        &accounts,
+        bank_fields.epoch,
+        &stakes.stake_history,
+        new_rate_activation_epoch(&accounts, &bank_fields.epoch_schedule),
+    );
+
+    let mut writer: BufWriter<Box<dyn Write>> = BufWriter::new(match output {
+        Some(output) => Box::new(File::create(output)?),
+        None => Box::new(io::stdout().lock()),
+    });
+    writeln!(writer, "vote_account,node_pubkey,commission,activated_stake,delegators")?;
+    for validator in &validators {
+        writeln!(
+            writer,
+            "{},{},{},{},{}",
+            validator.vote_account,
+            validator.node_pubkey,
+            validator.commission,
+            validator.activated_stake,
+            validator.delegators
+        )?;
+    }
+    writer.flush()?;
+
+    let activated_stake: u64 = validators.iter().map(|validator| validator.activated_stake).sum();
+    let bank_stake: u64 = stakes.vote_accounts.delegated_stakes().map(|(_, stake)| stake).sum();
+    info!(
+        "Activated stake {} lamports over {} vote accounts, {} lamports over {} vote accounts and {} delegations in the bank",
+        activated_stake,
+        validators.len(),
+        bank_stake,
+        stakes.vote_accounts.len(),
+        stakes.stake_delegations.len()
+    );
+    // The stakes cache holds the stake of its own epoch, which lags behind at an epoch boundary.
+    if stakes.epoch != bank_fields.epoch {
+        info!(
+            "Skipping comparison with the stakes of epoch {} in the bank",
+            stakes.epoch
+        );
+        return Ok(());
+    }
+    let mismatches = compare_with_bank(&validators, stakes);
+    for mismatch in &mismatches {
+        warn!("Stake mismatch: {}", mismatch);
+    }
+    if !mismatches.is_empty() {
+        anyhow::bail!(
+            "{} vote accounts do not match the stakes of the bank",
+            mismatches.len()
+        );
+    }
+    info!("Stakes match the bank");
+    Ok(())
+}
+
+async fn serve(
+    loader: SupportedLoader,
+    index_path: &Path,
+    account_indexes: &[AccountIndex],
+    bind: SocketAddr,
+) -> anyhow::Result<()> {
+    let SupportedLoader::Unpacked(extractor) = loader else {
+        anyhow::bail!("serve needs an unpacked snapshot directory as --snapshot-archive-path");
+    };
+    let mut accounts = IndexedAccounts::from_extractor(extractor, index_path)?;
+    accounts.open_secondary_indexes(index_path, account_indexes)?;
+    info!(
+        "Serving {} accounts at slot {}",
+        accounts.index().len(),
+        accounts.index().slot()
+    );
+    let handler = Arc::new(RpcHandler::new(accounts)?);
+    rpc::serve(handler, bind).await?;
+    Ok(())
+}
+
+fn verify_proof(proof: &Path, accounts_hash: &Hash) -> anyhow::Result<()> {
+    let proof: AccountProof = serde_json::from_reader(File::open(proof)?)?;
+    if !proof.verify(accounts_hash) {
+        anyhow::bail!(
+            "proof of account {} does not lead to accounts hash {}",
+            proof.pubkey,
+            accounts_hash
+        );
+    }
+    info!(
+        "Account {} with hash {} is included in accounts hash {}",
+        proof.pubkey, proof.account_hash, accounts_hash
+    );
+    Ok(())
+}
+
+fn verify(loader: &mut SupportedLoader, snapshot_archive_path: &Path) -> anyhow::Result<()> {
+    let accounts = LatestAccounts::from_extractor(loader)?;
+
+    let corrupt_accounts = verify_account_hashes(accounts.append_vecs())
+        .mismatches
+        .len();
+
+    // The manifest of an incremental snapshot only records the hash of its own slot range.
+    if loader.extra_fields().incremental_snapshot_persistence.is_none() {
+        let verification = AccountsHashVerification::new(
+            &accounts,
+            loader,
+            archive_snapshot_hash(snapshot_archive_path),
+        );
+        info!("{}", verification);
+        if !verification.matches_manifest() {
+            anyhow::bail!("accounts hash does not match the snapshot manifest");
+        }
+        if !verification.matches_archive() {
+            anyhow::bail!("snapshot hash does not match the archive file name");
+        }
+    } else {
+        info!("Skipping accounts hash of incremental snapshot");
+    }
+
+    let lt_hash_verification = AccountsLtHashVerification::new(&accounts, loader);
+    info!("{}", lt_hash_verification);
+    if lt_hash_verification.matches_manifest() == Some(false) {
+        anyhow::bail!("accounts lt hash does not match the snapshot manifest");
+    }
+
+    if corrupt_accounts > 0 {
+        anyhow::bail!("{} accounts do not match their stored hash", corrupt_accounts);
+    }
+    info!("Snapshot verified");
+    Ok(())
+}
+
+fn print_stats(loader: &mut SupportedLoader) -> anyhow::Result<()> {
     let mut accounts_per_slot: HashMap<Slot, u64> = HashMap::new();
     let mut updates: HashMap<Pubkey, Vec<Slot>> = HashMap::new();
 
@@ -72,7 +692,7 @@ async fn main() -> anyhow::Result<()> {
 
     for (count, group) in &updates.into_iter().map(|(pubkey, slots)| (pubkey, slots.len()))
         .sorted_by_key(|(_, count)| *count)
-        .group_by(|(pubkey, count)| *count) {
+        .group_by(|(_pubkey, count)| *count) {
         info!("count: {:?} groupsize: {}", count, group.count());
     }
 
@@ -84,6 +704,7 @@ pub enum SupportedLoader {
     Unpacked(UnpackedSnapshotExtractor),
     ArchiveFile(ArchiveSnapshotExtractor<File>),
     ArchiveDownload(ArchiveSnapshotExtractor<Response>),
+    Incremental(Box<IncrementalSnapshotExtractor<File>>),
 }
 
 impl SupportedLoader {
@@ -102,6 +723,12 @@ impl SupportedLoader {
         Ok(Self::ArchiveDownload(loader))
     }
 
+    fn new_incremental(full_path: &Path, incremental_path: &Path) -> anyhow::Result<Self> {
+        info!("Reading full and incremental snapshot archives");
+        let loader = IncrementalSnapshotExtractor::open(full_path, incremental_path)?;
+        Ok(Self::Incremental(Box::new(loader)))
+    }
+
     fn new_file(
         path: &Path,
         progress_tracking: Box<dyn ReadProgressTracking>,
@@ -122,10 +749,39 @@ impl SnapshotExtractor for SupportedLoader {
             SupportedLoader::Unpacked(loader) => Box::new(loader.iter()),
             SupportedLoader::ArchiveFile(loader) => Box::new(loader.iter()),
             SupportedLoader::ArchiveDownload(loader) => Box::new(loader.iter()),
+            SupportedLoader::Incremental(loader) => Box::new(loader.iter()),
+        }
+    }
+
+    fn bank_fields(&self) -> &DeserializableVersionedBank {
+        match self {
+            SupportedLoader::Unpacked(loader) => loader.bank_fields(),
+            SupportedLoader::ArchiveFile(loader) => loader.bank_fields(),
+            SupportedLoader::ArchiveDownload(loader) => loader.bank_fields(),
+            SupportedLoader::Incremental(loader) => loader.bank_fields(),
+        }
+    }
+
+    fn accounts_db_fields(&self) -> &AccountsDbFields<SerializableAccountStorageEntry> {
+        match self {
+            SupportedLoader::Unpacked(loader) => loader.accounts_db_fields(),
+            SupportedLoader::ArchiveFile(loader) => loader.accounts_db_fields(),
+            SupportedLoader::ArchiveDownload(loader) => loader.accounts_db_fields(),
+            SupportedLoader::Incremental(loader) => loader.accounts_db_fields(),
+        }
+    }
+
+    fn extra_fields(&self) -> &ExtraFieldsToDeserialize {
+        match self {
+            SupportedLoader::Unpacked(loader) => loader.extra_fields(),
+            SupportedLoader::ArchiveFile(loader) => loader.extra_fields(),
+            SupportedLoader::ArchiveDownload(loader) => loader.extra_fields(),
+            SupportedLoader::Incremental(loader) => loader.extra_fields(),
         }
     }
 }
 
+#[allow(dead_code)]
 struct SimpleLogConsumer {
 }
 
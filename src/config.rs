@@ -0,0 +1,244 @@
+use {
+    crate::{filter::AccountFilters, latest::LatestAccounts},
+    serde::Deserialize,
+    serde_json::{Map, Value},
+    solana_sdk::{
+        account::ReadableAccount, clock::Slot, config, pubkey, pubkey::Pubkey, short_vec,
+    },
+    std::collections::{btree_map::Entry, BTreeMap},
+};
+
+/// First key of validator info config accounts.
+pub const VALIDATOR_INFO_ID: Pubkey = pubkey!("Va1idator1nfo111111111111111111111111111111");
+
+/// First key of the stake config account.
+pub const STAKE_CONFIG_ID: Pubkey = pubkey!("StakeConfig11111111111111111111111111111111");
+
+/// The keys a config account starts with, with whether each key has to sign updates.
+#[derive(Deserialize)]
+struct ConfigKeys {
+    #[serde(with = "short_vec")]
+    keys: Vec<(Pubkey, bool)>,
+}
+
+/// Information a validator publishes about itself with `solana validator-info publish`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidatorInfo {
+    /// identity of the validator, which signs the info
+    pub identity: Pubkey,
+    pub name: Option<String>,
+    pub website: Option<String>,
+    pub details: Option<String>,
+    pub keybase_username: Option<String>,
+    pub icon_url: Option<String>,
+}
+
+/// The deprecated stake program configuration.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub struct StakeConfig {
+    pub warmup_cooldown_rate: f64,
+    pub slash_penalty: u8,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigAccount {
+    ValidatorInfo(ValidatorInfo),
+    StakeConfig(StakeConfig),
+    /// A config account of another kind, with the data following the keys.
+    Unknown {
+        keys: Vec<(Pubkey, bool)>,
+        data: Vec<u8>,
+    },
+}
+
+/// Decodes an account owned by the config program.
+pub fn decode_config_account(account: &impl ReadableAccount) -> Option<ConfigAccount> {
+    if *account.owner() != config::program::id() {
+        return None;
+    }
+    let mut data = account.data();
+    let ConfigKeys { keys } = bincode::deserialize_from(&mut data).ok()?;
+    Some(match keys.first() {
+        Some((id, _)) if *id == VALIDATOR_INFO_ID => {
+            ConfigAccount::ValidatorInfo(decode_validator_info(&keys, data)?)
+        }
+        Some((id, _)) if *id == STAKE_CONFIG_ID => {
+            ConfigAccount::StakeConfig(bincode::deserialize(data).ok()?)
+        }
+        _ => ConfigAccount::Unknown {
+            keys,
+            data: data.to_vec(),
+        },
+    })
+}
+
+/// Validator info stores a JSON string, signed by the identity in the second key.
+fn decode_validator_info(keys: &[(Pubkey, bool)], data: &[u8]) -> Option<ValidatorInfo> {
+    let (identity, _) = keys.get(1)?;
+    let info: String = bincode::deserialize(data).ok()?;
+    let info: Map<String, Value> = serde_json::from_str(&info).ok()?;
+    let field = |name: &str| info.get(name).and_then(Value::as_str).map(str::to_string);
+    Some(ValidatorInfo {
+        identity: *identity,
+        name: field("name"),
+        website: field("website"),
+        details: field("details"),
+        keybase_username: field("keybaseUsername"),
+        icon_url: field("iconUrl"),
+    })
+}
+
+/// Collects the validator info of the latest account set by validator identity, sorted by
+/// identity. A validator publishing from several info accounts keeps the info stored at the
+/// highest slot, and of those the one of the highest info account pubkey.
+pub fn validator_infos(accounts: &LatestAccounts) -> BTreeMap<Pubkey, ValidatorInfo> {
+    let filters = AccountFilters::new().with_owner(config::program::id());
+    let mut infos: BTreeMap<Pubkey, ((Slot, Pubkey), ValidatorInfo)> = BTreeMap::new();
+    for (slot, stored) in accounts.filter(&filters) {
+        let Some(ConfigAccount::ValidatorInfo(info)) = decode_config_account(&stored) else {
+            continue;
+        };
+        let version = (slot, stored.meta.pubkey);
+        match infos.entry(info.identity) {
+            Entry::Vacant(entry) => {
+                entry.insert((version, info));
+            }
+            Entry::Occupied(mut entry) => {
+                if version > entry.get().0 {
+                    entry.insert((version, info));
+                }
+            }
+        }
+    }
+    infos
+        .into_iter()
+        .map(|(identity, (_, info))| (identity, info))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{append_vec::test_utils::TestAccount, test_utils::TestExtractor},
+        serde::Serialize,
+        solana_sdk::account::{Account, AccountSharedData, WritableAccount},
+    };
+
+    #[derive(Serialize)]
+    struct Keys {
+        #[serde(with = "short_vec")]
+        keys: Vec<(Pubkey, bool)>,
+    }
+
+    /// A config account with the keys and data, padded like accounts created with extra space.
+    fn config_account(keys: Vec<(Pubkey, bool)>, data: &impl Serialize) -> Account {
+        let mut account_data = bincode::serialize(&Keys { keys }).unwrap();
+        account_data.extend(bincode::serialize(data).unwrap());
+        account_data.resize(account_data.len() + 100, 0);
+        Account::create(1, account_data, config::program::id(), false, 0)
+    }
+
+    fn info_account(identity: Pubkey, name: &str) -> Account {
+        let info = serde_json::json!({
+            "name": name,
+            "website": "https://example.com",
+            "keybaseUsername": "validator",
+        });
+        config_account(
+            vec![(VALIDATOR_INFO_ID, false), (identity, true)],
+            &info.to_string(),
+        )
+    }
+
+    #[test]
+    fn decodes_config_accounts() {
+        let identity = Pubkey::new_unique();
+        assert_eq!(
+            decode_config_account(&info_account(identity, "Validator")),
+            Some(ConfigAccount::ValidatorInfo(ValidatorInfo {
+                identity,
+                name: Some("Validator".to_string()),
+                website: Some("https://example.com".to_string()),
+                details: None,
+                keybase_username: Some("validator".to_string()),
+                icon_url: None,
+            }))
+        );
+
+        let stake_config = config_account(vec![(STAKE_CONFIG_ID, false)], &(0.25f64, 12u8));
+        assert_eq!(
+            decode_config_account(&stake_config),
+            Some(ConfigAccount::StakeConfig(StakeConfig {
+                warmup_cooldown_rate: 0.25,
+                slash_penalty: 12,
+            }))
+        );
+
+        let key = Pubkey::new_unique();
+        let unknown = config_account(vec![(key, true)], &7u8);
+        assert!(matches!(
+            decode_config_account(&unknown),
+            Some(ConfigAccount::Unknown { keys, data }) if keys == [(key, true)] && data[0] == 7
+        ));
+
+        // info that is not a JSON object, or without the identity key
+        let not_json = config_account(
+            vec![(VALIDATOR_INFO_ID, false), (identity, true)],
+            &"not json".to_string(),
+        );
+        assert_eq!(decode_config_account(&not_json), None);
+        let no_identity = config_account(vec![(VALIDATOR_INFO_ID, false)], &"{}".to_string());
+        assert_eq!(decode_config_account(&no_identity), None);
+        let mut other_owner = info_account(identity, "Validator");
+        other_owner.owner = Pubkey::new_unique();
+        assert_eq!(decode_config_account(&other_owner), None);
+    }
+
+    #[test]
+    fn selects_latest_info_of_each_identity() {
+        let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut tied = [Pubkey::new_unique(), Pubkey::new_unique()];
+        tied.sort();
+        let info = |pubkey, identity, name| {
+            TestAccount::new(
+                pubkey,
+                AccountSharedData::from(info_account(identity, name)),
+            )
+        };
+        let files = vec![
+            (
+                5,
+                1,
+                vec![
+                    info(Pubkey::new_unique(), first, "first at slot 5"),
+                    info(tied[1], second, "second, higher pubkey"),
+                    info(tied[0], second, "second, lower pubkey"),
+                ],
+            ),
+            (
+                4,
+                1,
+                vec![info(Pubkey::new_unique(), first, "first at slot 4")],
+            ),
+        ];
+        let mut extractor = TestExtractor::new(files);
+        let accounts = LatestAccounts::from_extractor(&mut extractor).unwrap();
+        let names = |accounts| -> Vec<_> {
+            validator_infos(accounts)
+                .into_iter()
+                .map(|(identity, info)| (identity, info.name.unwrap()))
+                .collect()
+        };
+        let mut expected = vec![
+            (first, "first at slot 5".to_string()),
+            (second, "second, higher pubkey".to_string()),
+        ];
+        expected.sort();
+        assert_eq!(names(&accounts), expected);
+
+        extractor.files.reverse();
+        let accounts = LatestAccounts::from_extractor(&mut extractor).unwrap();
+        assert_eq!(names(&accounts), expected);
+    }
+}
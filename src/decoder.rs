@@ -0,0 +1,240 @@
+use {
+    log::info,
+    solana_runtime::snapshot_utils::ArchiveFormat,
+    std::{
+        io::{self, BufReader, Chain, Cursor, Read},
+        path::Path,
+    },
+};
+
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const BZIP2_MAGIC: &[u8] = b"BZh";
+const LZ4_MAGIC: &[u8] = &[0x04, 0x22, 0x4d, 0x18];
+/// Length of the longest magic number.
+const MAGIC_LEN: u64 = 4;
+
+/// The archive stream, behind the magic bytes already read to detect its format.
+type Stream<Source> = BufReader<Chain<Cursor<Vec<u8>>, Source>>;
+
+/// Decompresses a snapshot archive stream in any of the formats the validator has produced.
+pub enum ArchiveDecoder<Source>
+where
+    Source: Read,
+{
+    Zstd(zstd::Decoder<'static, Stream<Source>>),
+    Gzip(flate2::read::GzDecoder<Stream<Source>>),
+    Bzip2(bzip2::read::BzDecoder<Stream<Source>>),
+    Lz4(lz4::Decoder<Stream<Source>>),
+    Tar(Stream<Source>),
+}
+
+impl<Source> ArchiveDecoder<Source>
+where
+    Source: Read,
+{
+    pub fn new(source: Source, archive_format: ArchiveFormat) -> io::Result<Self> {
+        Self::with_buffer(
+            BufReader::new(Cursor::new(Vec::new()).chain(source)),
+            archive_format,
+        )
+    }
+
+    /// Picks the decompressor from the magic bytes at the start of the stream.
+    /// Streams without a known magic number are read as uncompressed tar.
+    pub fn detect(mut source: Source) -> io::Result<Self> {
+        // reads may return fewer bytes than asked for, so read until EOF or the whole header
+        let mut header = Vec::new();
+        source.by_ref().take(MAGIC_LEN).read_to_end(&mut header)?;
+        let archive_format = detect_archive_format(&header);
+        info!("Detected snapshot archive format {}", archive_format);
+        Self::with_buffer(
+            BufReader::new(Cursor::new(header).chain(source)),
+            archive_format,
+        )
+    }
+
+    fn with_buffer(source: Stream<Source>, archive_format: ArchiveFormat) -> io::Result<Self> {
+        Ok(match archive_format {
+            ArchiveFormat::TarZstd { .. } => Self::Zstd(zstd::Decoder::with_buffer(source)?),
+            ArchiveFormat::TarGzip => Self::Gzip(flate2::read::GzDecoder::new(source)),
+            ArchiveFormat::TarBzip2 => Self::Bzip2(bzip2::read::BzDecoder::new(source)),
+            ArchiveFormat::TarLz4 => Self::Lz4(lz4::Decoder::new(source)?),
+            ArchiveFormat::Tar => Self::Tar(source),
+        })
+    }
+}
+
+impl<Source> Read for ArchiveDecoder<Source>
+where
+    Source: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Zstd(decoder) => decoder.read(buf),
+            Self::Gzip(decoder) => decoder.read(buf),
+            Self::Bzip2(decoder) => decoder.read(buf),
+            Self::Lz4(decoder) => decoder.read(buf),
+            Self::Tar(reader) => reader.read(buf),
+        }
+    }
+}
+
+fn detect_archive_format(header: &[u8]) -> ArchiveFormat {
+    if header.starts_with(ZSTD_MAGIC) {
+        ArchiveFormat::TarZstd {
+            config: Default::default(),
+        }
+    } else if header.starts_with(GZIP_MAGIC) {
+        ArchiveFormat::TarGzip
+    } else if header.starts_with(BZIP2_MAGIC) {
+        ArchiveFormat::TarBzip2
+    } else if header.starts_with(LZ4_MAGIC) {
+        ArchiveFormat::TarLz4
+    } else {
+        ArchiveFormat::Tar
+    }
+}
+
+/// Picks the archive format from the file extension, e.g. `.tar.zst` or `.tar.bz2`.
+pub fn archive_format_from_path(path: &Path) -> Option<ArchiveFormat> {
+    let file_name = path.file_name()?.to_str()?;
+    let (_, extension) = file_name.split_once(".tar")?;
+    format!("tar{}", extension).parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::io::Write};
+
+    const PAYLOAD: &[u8] = b"a tar stream, or anything else the archive contains";
+
+    fn zstd_default() -> ArchiveFormat {
+        ArchiveFormat::TarZstd {
+            config: Default::default(),
+        }
+    }
+
+    fn compress(archive_format: ArchiveFormat) -> Vec<u8> {
+        match archive_format {
+            ArchiveFormat::TarZstd { .. } => zstd::encode_all(PAYLOAD, 0).unwrap(),
+            ArchiveFormat::TarGzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(PAYLOAD).unwrap();
+                encoder.finish().unwrap()
+            }
+            ArchiveFormat::TarBzip2 => {
+                let mut encoder =
+                    bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+                encoder.write_all(PAYLOAD).unwrap();
+                encoder.finish().unwrap()
+            }
+            ArchiveFormat::TarLz4 => {
+                let mut encoder = lz4::EncoderBuilder::new().build(Vec::new()).unwrap();
+                encoder.write_all(PAYLOAD).unwrap();
+                let (compressed, result) = encoder.finish();
+                result.unwrap();
+                compressed
+            }
+            ArchiveFormat::Tar => PAYLOAD.to_vec(),
+        }
+    }
+
+    fn all_formats() -> [ArchiveFormat; 5] {
+        [
+            zstd_default(),
+            ArchiveFormat::TarGzip,
+            ArchiveFormat::TarBzip2,
+            ArchiveFormat::TarLz4,
+            ArchiveFormat::Tar,
+        ]
+    }
+
+    #[test]
+    fn detects_compressed_formats() {
+        for archive_format in all_formats() {
+            let compressed = compress(archive_format);
+            assert_eq!(
+                detect_archive_format(&compressed),
+                archive_format,
+                "{}",
+                archive_format
+            );
+        }
+    }
+
+    #[test]
+    fn decodes_detected_formats() {
+        for archive_format in all_formats() {
+            let mut decoder =
+                ArchiveDecoder::detect(Cursor::new(compress(archive_format))).unwrap();
+            let mut decoded = Vec::new();
+            decoder.read_to_end(&mut decoded).unwrap();
+            assert_eq!(decoded, PAYLOAD, "{}", archive_format);
+        }
+    }
+
+    /// Returns a single byte per read.
+    struct ByteReader(Cursor<Vec<u8>>);
+
+    impl Read for ByteReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(1);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    #[test]
+    fn detects_formats_from_short_reads() {
+        for archive_format in all_formats() {
+            let source = ByteReader(Cursor::new(compress(archive_format)));
+            let mut decoder = ArchiveDecoder::detect(source).unwrap();
+            let mut decoded = Vec::new();
+            decoder.read_to_end(&mut decoded).unwrap();
+            assert_eq!(decoded, PAYLOAD, "{}", archive_format);
+        }
+    }
+
+    #[test]
+    fn reads_unknown_and_short_headers_as_tar() {
+        assert_eq!(detect_archive_format(&[]), ArchiveFormat::Tar);
+        assert_eq!(detect_archive_format(b"ustar"), ArchiveFormat::Tar);
+        // prefixes of the magic numbers
+        assert_eq!(detect_archive_format(&ZSTD_MAGIC[..3]), ArchiveFormat::Tar);
+        assert_eq!(detect_archive_format(&GZIP_MAGIC[..1]), ArchiveFormat::Tar);
+        assert_eq!(detect_archive_format(b"BZ"), ArchiveFormat::Tar);
+        assert_eq!(detect_archive_format(&LZ4_MAGIC[..3]), ArchiveFormat::Tar);
+
+        let mut decoder = ArchiveDecoder::detect(Cursor::new(Vec::new())).unwrap();
+        let mut decoded = Vec::new();
+        decoder.read_to_end(&mut decoded).unwrap();
+        assert!(decoded.is_empty());
+    }
+
+    #[test]
+    fn archive_format_from_extension() {
+        let cases = [
+            ("snapshot-100-hash.tar.zst", Some(zstd_default())),
+            ("snapshot-100-hash.tar.gz", Some(ArchiveFormat::TarGzip)),
+            ("snapshot-100-hash.tar.bz2", Some(ArchiveFormat::TarBzip2)),
+            ("snapshot-100-hash.tar.lz4", Some(ArchiveFormat::TarLz4)),
+            ("snapshot-100-hash.tar", Some(ArchiveFormat::Tar)),
+            (
+                "/snapshots/incremental-snapshot-1-2-hash.tar.zst",
+                Some(zstd_default()),
+            ),
+            ("snapshot-100-hash.tar.xz", None),
+            ("snapshot-100-hash.zip", None),
+            ("snapshot", None),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(
+                archive_format_from_path(Path::new(path)),
+                expected,
+                "{}",
+                path
+            );
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use {
+    crate::{
+        append_vec::{AppendVec, StoredAccountMeta},
+        append_vec_iter,
+    },
+    solana_sdk::{account::ReadableAccount, pubkey::Pubkey},
+    std::collections::HashSet,
+};
+
+/// Compares the account data at `offset` with `bytes`, like the RPC `memcmp` filter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemcmpFilter {
+    pub offset: usize,
+    pub bytes: Vec<u8>,
+}
+
+impl MemcmpFilter {
+    pub const fn new(offset: usize, bytes: Vec<u8>) -> Self {
+        Self { offset, bytes }
+    }
+
+    /// Data shorter than `offset + bytes.len()` never matches.
+    pub fn bytes_match(&self, data: &[u8]) -> bool {
+        data.get(self.offset..)
+            .and_then(|data| data.get(..self.bytes.len()))
+            .is_some_and(|data| data == self.bytes.as_slice())
+    }
+}
+
+/// Filters accounts with the semantics of the RPC `getProgramAccounts` filters: an account
+/// matches if it passes every filter that is set.
+///
+/// Filters look at the stored account in place, so accounts that do not match are skipped
+/// without copying their data.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccountFilters {
+    owner: Option<Pubkey>,
+    pubkeys: Option<HashSet<Pubkey>>,
+    data_size: Option<u64>,
+    memcmp: Vec<MemcmpFilter>,
+}
+
+impl AccountFilters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only accounts owned by `owner`, the program of `getProgramAccounts`.
+    pub const fn with_owner(mut self, owner: Pubkey) -> Self {
+        self.owner = Some(owner);
+        self
+    }
+
+    /// Only accounts with one of the given pubkeys.
+    pub fn with_pubkeys(mut self, pubkeys: impl IntoIterator<Item = Pubkey>) -> Self {
+        self.pubkeys = Some(pubkeys.into_iter().collect());
+        self
+    }
+
+    /// Only accounts with exactly `data_size` bytes of data.
+    pub const fn with_data_size(mut self, data_size: u64) -> Self {
+        self.data_size = Some(data_size);
+        self
+    }
+
+    /// Only accounts whose data at `offset` starts with `bytes`. Can be given multiple times.
+    pub fn with_memcmp(mut self, offset: usize, bytes: Vec<u8>) -> Self {
+        self.memcmp.push(MemcmpFilter::new(offset, bytes));
+        self
+    }
+
+    pub const fn owner(&self) -> Option<&Pubkey> {
+        self.owner.as_ref()
+    }
+
+    pub const fn pubkeys(&self) -> Option<&HashSet<Pubkey>> {
+        self.pubkeys.as_ref()
+    }
+
+    pub const fn data_size(&self) -> Option<u64> {
+        self.data_size
+    }
+
+    pub fn memcmp(&self) -> &[MemcmpFilter] {
+        self.memcmp.as_slice()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.owner.is_none()
+            && self.pubkeys.is_none()
+            && self.data_size.is_none()
+            && self.memcmp.is_empty()
+    }
+
+    /// Whether the account matches all filters.
+    pub fn matches(&self, stored: &StoredAccountMeta) -> bool {
+        self.matches_account(&stored.meta.pubkey, stored)
+    }
+
+    /// Whether the account with `pubkey` matches all filters.
+    pub fn matches_account(&self, pubkey: &Pubkey, account: &impl ReadableAccount) -> bool {
+        self.pubkeys
+            .as_ref()
+            .is_none_or(|pubkeys| pubkeys.contains(pubkey))
+            && self.owner.is_none_or(|owner| owner == *account.owner())
+            && self
+                .data_size
+                .is_none_or(|data_size| data_size == account.data().len() as u64)
+            && self
+                .memcmp
+                .iter()
+                .all(|memcmp| memcmp.bytes_match(account.data()))
+    }
+
+    /// Iterates over the accounts of the file matching all filters, in storage order.
+    pub fn iter<'a>(
+        &'a self,
+        append_vec: &'a AppendVec,
+    ) -> impl Iterator<Item = StoredAccountMeta<'a>> + 'a {
+        append_vec_iter(append_vec)
+            .filter_map(|handle| handle.access())
+            .filter(|stored| self.matches(stored))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_accounts_db::account_storage::meta::{AccountMeta, StoredMeta},
+        solana_sdk::hash::Hash,
+    };
+
+    /// The metas are leaked to be borrowed like from an AppendVec.
+    fn stored(pubkey: Pubkey, owner: Pubkey, data: &[u8]) -> StoredAccountMeta<'_> {
+        StoredAccountMeta {
+            meta: Box::leak(Box::new(StoredMeta {
+                write_version_obsolete: 0,
+                data_len: data.len() as u64,
+                pubkey,
+            })),
+            account_meta: Box::leak(Box::new(AccountMeta {
+                lamports: 1,
+                rent_epoch: 0,
+                owner,
+                executable: false,
+            })),
+            data,
+            offset: 0,
+            stored_size: 0,
+            hash: Box::leak(Box::new(Hash::default())),
+        }
+    }
+
+    #[test]
+    fn empty_filters_match_everything() {
+        let filters = AccountFilters::new();
+        assert!(filters.is_empty());
+        assert!(filters.matches(&stored(Pubkey::new_unique(), Pubkey::new_unique(), &[])));
+    }
+
+    #[test]
+    fn owner() {
+        let owner = Pubkey::new_unique();
+        let filters = AccountFilters::new().with_owner(owner);
+        assert!(!filters.is_empty());
+        assert!(filters.matches(&stored(Pubkey::new_unique(), owner, &[])));
+        assert!(!filters.matches(&stored(Pubkey::new_unique(), Pubkey::new_unique(), &[])));
+    }
+
+    #[test]
+    fn pubkeys() {
+        let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let owner = Pubkey::new_unique();
+        let filters = AccountFilters::new().with_pubkeys([first, second]);
+        assert!(filters.matches(&stored(first, owner, &[])));
+        assert!(filters.matches(&stored(second, owner, &[])));
+        assert!(!filters.matches(&stored(Pubkey::new_unique(), owner, &[])));
+
+        // an empty set matches no account, unlike no pubkey filter
+        let filters = AccountFilters::new().with_pubkeys([]);
+        assert!(!filters.is_empty());
+        assert!(!filters.matches(&stored(first, owner, &[])));
+    }
+
+    #[test]
+    fn data_size() {
+        let owner = Pubkey::new_unique();
+        let filters = AccountFilters::new().with_data_size(3);
+        assert!(filters.matches(&stored(Pubkey::new_unique(), owner, &[1, 2, 3])));
+        assert!(!filters.matches(&stored(Pubkey::new_unique(), owner, &[1, 2])));
+        assert!(!filters.matches(&stored(Pubkey::new_unique(), owner, &[1, 2, 3, 4])));
+
+        let filters = AccountFilters::new().with_data_size(0);
+        assert!(filters.matches(&stored(Pubkey::new_unique(), owner, &[])));
+    }
+
+    #[test]
+    fn memcmp() {
+        let owner = Pubkey::new_unique();
+        let data = [1, 2, 3, 4];
+        let matches = |offset: usize, bytes: &[u8]| {
+            AccountFilters::new()
+                .with_memcmp(offset, bytes.to_vec())
+                .matches(&stored(Pubkey::new_unique(), owner, &data))
+        };
+        assert!(matches(0, &[1, 2]));
+        assert!(matches(2, &[3, 4]));
+        assert!(matches(0, &data));
+        assert!(!matches(1, &[1, 2]));
+        // bytes running past the end of the data
+        assert!(!matches(3, &[4, 5]));
+        assert!(!matches(0, &[1, 2, 3, 4, 5]));
+        // nothing to compare at the end of the data, but not beyond it
+        assert!(matches(4, &[]));
+        assert!(!matches(5, &[]));
+    }
+
+    #[test]
+    fn all_memcmp_filters_have_to_match() {
+        let owner = Pubkey::new_unique();
+        let account = stored(Pubkey::new_unique(), owner, &[1, 2, 3, 4]);
+        let filters = AccountFilters::new()
+            .with_memcmp(0, vec![1])
+            .with_memcmp(3, vec![4]);
+        assert_eq!(filters.memcmp().len(), 2);
+        assert!(filters.matches(&account));
+        assert!(!filters.with_memcmp(1, vec![3]).matches(&account));
+    }
+
+    #[test]
+    fn combined_filters() {
+        let (pubkey, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let data = [7, 8, 9];
+        let filters = AccountFilters::new()
+            .with_owner(owner)
+            .with_pubkeys([pubkey])
+            .with_data_size(3)
+            .with_memcmp(1, vec![8, 9]);
+        assert!(filters.matches(&stored(pubkey, owner, &data)));
+        assert!(!filters.matches(&stored(pubkey, Pubkey::new_unique(), &data)));
+        assert!(!filters.matches(&stored(Pubkey::new_unique(), owner, &data)));
+        assert!(!filters.matches(&stored(pubkey, owner, &[7, 8, 9, 0])));
+        assert!(!filters.matches(&stored(pubkey, owner, &[7, 9, 8])));
+    }
+}
@@ -0,0 +1,367 @@
+use {
+    crate::{
+        append_vec::StoredAccountMeta,
+        latest::{LatestAccounts, LatestPositions},
+        solana::DeserializableVersionedBank,
+        SnapshotError, SnapshotExtractor,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::{
+        GeyserPlugin, GeyserPluginError, ReplicaAccountInfoV3, ReplicaAccountInfoVersions,
+        SlotStatus,
+    },
+    libloading::{Library, Symbol},
+    log::info,
+    serde_json::Value,
+    solana_sdk::clock::Slot,
+    std::{
+        fs,
+        path::{Path, PathBuf},
+        time::Instant,
+    },
+    thiserror::Error,
+};
+
+/// Signature of the `_create_plugin` function a plugin library exports.
+type PluginConstructor = unsafe fn() -> *mut dyn GeyserPlugin;
+
+#[derive(Error, Debug)]
+pub enum GeyserError {
+    #[error("{0}")]
+    IOError(#[from] std::io::Error),
+    #[error("Failed to parse plugin config: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Plugin config has no libpath")]
+    MissingLibpath,
+    #[error("Failed to load plugin library: {0}")]
+    LibraryError(#[from] libloading::Error),
+    #[error("Plugin error: {0}")]
+    PluginError(#[from] GeyserPluginError),
+    #[error("{0}")]
+    SnapshotError(#[from] SnapshotError),
+}
+
+pub type GeyserResult<T> = Result<T, GeyserError>;
+
+/// A Geyser plugin loaded from its shared library, like the validator loads the plugins given
+/// with `--geyser-plugin-config`.
+#[derive(Debug)]
+pub struct LoadedGeyserPlugin {
+    plugin: Box<dyn GeyserPlugin>,
+    /// dropped after the plugin, whose code lives in the library
+    _library: Library,
+}
+
+impl LoadedGeyserPlugin {
+    /// Loads the library at the `libpath` of the JSON config, relative to the config file, and
+    /// calls `on_load` with the config file.
+    ///
+    /// # Safety
+    /// Runs the code of the library, which has to export `_create_plugin` returning a plugin
+    /// built against the same version of the plugin interface.
+    pub unsafe fn load(config_file: &Path) -> GeyserResult<Self> {
+        let config: Value = serde_json::from_str(&fs::read_to_string(config_file)?)?;
+        let libpath = PathBuf::from(
+            config
+                .get("libpath")
+                .and_then(Value::as_str)
+                .ok_or(GeyserError::MissingLibpath)?,
+        );
+        let libpath = match config_file.parent() {
+            Some(config_dir) if libpath.is_relative() => config_dir.join(libpath),
+            _ => libpath,
+        };
+
+        let library = Library::new(&libpath)?;
+        let constructor: Symbol<PluginConstructor> = library.get(b"_create_plugin")?;
+        let mut plugin = Box::from_raw(constructor());
+        plugin.setup_logger(log::logger(), log::max_level())?;
+        plugin.on_load(&config_file.to_string_lossy(), false)?;
+        info!(
+            "Loaded Geyser plugin {} from {}",
+            plugin.name(),
+            libpath.display()
+        );
+        Ok(Self {
+            plugin,
+            _library: library,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.plugin.name()
+    }
+
+    pub fn plugin(&self) -> &dyn GeyserPlugin {
+        self.plugin.as_ref()
+    }
+
+    pub fn plugin_mut(&mut self) -> &mut dyn GeyserPlugin {
+        self.plugin.as_mut()
+    }
+}
+
+/// Sends the latest copy of every account of the snapshot to `plugin` as the validator does at
+/// startup, then notifies the end of startup and the status of the snapshot slot. Returns the
+/// number of accounts sent.
+///
+/// The accounts at `positions`, resolved with [`LatestPositions::resolve`], are streamed from
+/// the accounts files of `extractor` one file at a time. As an archive can be read only once,
+/// `extractor` has to be opened again after resolving the positions.
+///
+/// Accounts are sent in the order they are stored, each with the slot it was stored in and a
+/// write version counting up from zero.
+pub fn replay_snapshot(
+    plugin: &mut dyn GeyserPlugin,
+    positions: &LatestPositions,
+    extractor: &mut impl SnapshotExtractor,
+) -> GeyserResult<u64> {
+    let started = Instant::now();
+    let mut write_version = 0;
+    if plugin.account_data_notifications_enabled() {
+        positions.for_each_account(extractor, |slot, stored| {
+            update_account(plugin, slot, stored, write_version)?;
+            write_version += 1;
+            Ok::<_, GeyserError>(())
+        })?;
+    }
+    end_of_startup(plugin, extractor.bank_fields(), write_version, started)
+}
+
+/// Sends the accounts like [`replay_snapshot`], in no particular order, for the snapshot of
+/// `bank_fields`.
+pub fn replay_accounts(
+    plugin: &mut dyn GeyserPlugin,
+    accounts: &LatestAccounts,
+    bank_fields: &DeserializableVersionedBank,
+) -> GeyserResult<u64> {
+    let started = Instant::now();
+    let mut write_version = 0;
+    if plugin.account_data_notifications_enabled() {
+        for (slot, handle) in accounts.iter() {
+            let Some(stored) = handle.access() else {
+                continue;
+            };
+            update_account(plugin, slot, &stored, write_version)?;
+            write_version += 1;
+        }
+    }
+    end_of_startup(plugin, bank_fields, write_version, started)
+}
+
+fn update_account(
+    plugin: &mut dyn GeyserPlugin,
+    slot: Slot,
+    stored: &StoredAccountMeta<'_>,
+    write_version: u64,
+) -> GeyserResult<()> {
+    let account = ReplicaAccountInfoV3 {
+        pubkey: stored.meta.pubkey.as_ref(),
+        lamports: stored.account_meta.lamports,
+        owner: stored.account_meta.owner.as_ref(),
+        executable: stored.account_meta.executable,
+        rent_epoch: stored.account_meta.rent_epoch,
+        data: stored.data,
+        write_version,
+        txn: None,
+    };
+    plugin.update_account(ReplicaAccountInfoVersions::V0_0_3(&account), slot, true)?;
+    Ok(())
+}
+
+/// The snapshot slot is rooted, so it is reported processed, confirmed and rooted for plugins
+/// that wait for any of these before committing the accounts of a slot.
+fn end_of_startup(
+    plugin: &mut dyn GeyserPlugin,
+    bank_fields: &DeserializableVersionedBank,
+    sent: u64,
+    started: Instant,
+) -> GeyserResult<u64> {
+    plugin.notify_end_of_startup()?;
+    for status in [
+        SlotStatus::Processed,
+        SlotStatus::Confirmed,
+        SlotStatus::Rooted,
+    ] {
+        plugin.update_slot_status(bank_fields.slot, Some(bank_fields.parent_slot), &status)?;
+    }
+    info!(
+        "Sent {} accounts to Geyser plugin {} in {:?}",
+        sent,
+        plugin.name(),
+        started.elapsed()
+    );
+    Ok(sent)
+}
+
+impl Drop for LoadedGeyserPlugin {
+    fn drop(&mut self) {
+        self.plugin.on_unload();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::archived::{test_utils::*, ArchiveSnapshotExtractor},
+        agave_geyser_plugin_interface::geyser_plugin_interface::Result as PluginResult,
+        solana_runtime::{bank::Bank, snapshot_utils::ArchiveFormat},
+        solana_sdk::{
+            account::{AccountSharedData, WritableAccount},
+            pubkey::Pubkey,
+        },
+        std::sync::Mutex,
+    };
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    enum Call {
+        UpdateAccount {
+            pubkey: Pubkey,
+            slot: Slot,
+            data: Vec<u8>,
+            write_version: u64,
+            is_startup: bool,
+        },
+        EndOfStartup,
+        SlotStatus(Slot, Option<Slot>, SlotStatus),
+    }
+
+    /// Records the calls of the replay.
+    #[derive(Debug, Default)]
+    struct MockPlugin {
+        accounts_disabled: bool,
+        calls: Mutex<Vec<Call>>,
+    }
+
+    impl MockPlugin {
+        fn calls(&self) -> Vec<Call> {
+            self.calls.lock().unwrap().clone()
+        }
+
+        fn record(&self, call: Call) -> PluginResult<()> {
+            self.calls.lock().unwrap().push(call);
+            Ok(())
+        }
+    }
+
+    impl GeyserPlugin for MockPlugin {
+        fn name(&self) -> &'static str {
+            "mock"
+        }
+
+        fn update_account(
+            &self,
+            account: ReplicaAccountInfoVersions,
+            slot: Slot,
+            is_startup: bool,
+        ) -> PluginResult<()> {
+            let ReplicaAccountInfoVersions::V0_0_3(account) = account else {
+                panic!("unexpected account info version");
+            };
+            self.record(Call::UpdateAccount {
+                pubkey: Pubkey::try_from(account.pubkey).unwrap(),
+                slot,
+                data: account.data.to_vec(),
+                write_version: account.write_version,
+                is_startup,
+            })
+        }
+
+        fn notify_end_of_startup(&self) -> PluginResult<()> {
+            self.record(Call::EndOfStartup)
+        }
+
+        fn update_slot_status(
+            &self,
+            slot: Slot,
+            parent: Option<u64>,
+            status: &SlotStatus,
+        ) -> PluginResult<()> {
+            self.record(Call::SlotStatus(slot, parent, status.clone()))
+        }
+
+        fn account_data_notifications_enabled(&self) -> bool {
+            !self.accounts_disabled
+        }
+    }
+
+    fn store(bank: &Bank, pubkey: &Pubkey, data: Vec<u8>) {
+        let account = AccountSharedData::create(1_000_000, data, Pubkey::new_unique(), false, 0);
+        bank.store_account(pubkey, &account);
+    }
+
+    /// Replays a snapshot at slot 3 with an account stored at slot 0 and updated at slot 3, and
+    /// an account stored at slot 3 only.
+    fn replay(plugin: &mut MockPlugin) -> (u64, u64, Pubkey, Pubkey) {
+        let dir = tempfile::tempdir().unwrap();
+        let (updated, created) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let bank = new_bank();
+        store(&bank, &updated, vec![1]);
+        let bank = new_child_bank(bank, 3);
+        store(&bank, &updated, vec![2, 2]);
+        store(&bank, &created, vec![3; 3]);
+        let archive = write_full_snapshot_archive(&bank, dir.path(), ArchiveFormat::Tar);
+
+        let positions =
+            LatestPositions::resolve(&mut ArchiveSnapshotExtractor::open(&archive).unwrap())
+                .unwrap();
+        let mut extractor = ArchiveSnapshotExtractor::open(&archive).unwrap();
+        let sent = replay_snapshot(plugin, &positions, &mut extractor).unwrap();
+        (sent, positions.len() as u64, updated, created)
+    }
+
+    fn end_of_startup_calls() -> Vec<Call> {
+        vec![
+            Call::EndOfStartup,
+            Call::SlotStatus(3, Some(0), SlotStatus::Processed),
+            Call::SlotStatus(3, Some(0), SlotStatus::Confirmed),
+            Call::SlotStatus(3, Some(0), SlotStatus::Rooted),
+        ]
+    }
+
+    #[test]
+    fn replays_latest_accounts_then_end_of_startup() {
+        let mut plugin = MockPlugin::default();
+        let (sent, latest, updated, created) = replay(&mut plugin);
+        assert_eq!(sent, latest);
+
+        let calls = plugin.calls();
+        let (updates, end) = calls.split_at(calls.len() - 4);
+        assert_eq!(end, end_of_startup_calls());
+        assert_eq!(updates.len() as u64, sent);
+        let mut updated_calls = Vec::new();
+        for (expected_write_version, call) in (0..).zip(updates) {
+            let Call::UpdateAccount {
+                pubkey,
+                slot,
+                data,
+                write_version,
+                is_startup,
+            } = call
+            else {
+                panic!("unexpected call {:?}", call);
+            };
+            assert_eq!(*write_version, expected_write_version);
+            assert!(is_startup);
+            if *pubkey == updated || *pubkey == created {
+                updated_calls.push((*pubkey, *slot, data.clone()));
+            }
+        }
+        // only the latest copy of the updated account is sent
+        updated_calls.sort();
+        let mut expected = vec![(updated, 3, vec![2, 2]), (created, 3, vec![3; 3])];
+        expected.sort();
+        assert_eq!(updated_calls, expected);
+    }
+
+    #[test]
+    fn skips_accounts_if_notifications_are_disabled() {
+        let mut plugin = MockPlugin {
+            accounts_disabled: true,
+            ..MockPlugin::default()
+        };
+        let (sent, _, _, _) = replay(&mut plugin);
+        assert_eq!(sent, 0);
+        assert_eq!(plugin.calls(), end_of_startup_calls());
+    }
+}
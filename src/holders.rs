@@ -0,0 +1,206 @@
+use {
+    crate::{filter::AccountFilters, latest::LatestAccounts, token::decode_token},
+    log::info,
+    solana_sdk::pubkey::Pubkey,
+    std::{cmp::Reverse, collections::HashMap, time::Instant},
+};
+
+/// A token account holding a balance of a mint.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenHolder {
+    /// wallet owning the token account
+    pub owner: Pubkey,
+    pub token_account: Pubkey,
+    pub amount: u64,
+    pub delegate: Option<Pubkey>,
+    pub delegated_amount: u64,
+}
+
+/// The combined balance of all token accounts of an owner.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OwnerBalance {
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub token_accounts: usize,
+}
+
+/// Finds all token accounts of `mint`, under both token programs. Token accounts with a zero
+/// balance are included unless `skip_empty` is set.
+/// Holders are sorted by descending balance, ties by token account.
+pub fn token_holders(
+    accounts: &LatestAccounts,
+    mint: &Pubkey,
+    skip_empty: bool,
+) -> Vec<TokenHolder> {
+    let started = Instant::now();
+    // the mint is the first field of a token account
+    let filters = AccountFilters::new().with_memcmp(0, mint.to_bytes().to_vec());
+    let mut holders: Vec<TokenHolder> = accounts
+        .filter(&filters)
+        .filter_map(|(_, stored)| {
+            let token = decode_token(&stored)?.account;
+            (token.mint == *mint && !(skip_empty && token.amount == 0)).then(|| TokenHolder {
+                owner: token.owner,
+                token_account: stored.meta.pubkey,
+                amount: token.amount,
+                delegate: token.delegate.into(),
+                delegated_amount: token.delegated_amount,
+            })
+        })
+        .collect();
+    holders.sort_unstable_by_key(|holder| (Reverse(holder.amount), holder.token_account));
+    info!(
+        "Found {} holders of mint {} in {:?}",
+        holders.len(),
+        mint,
+        started.elapsed()
+    );
+    holders
+}
+
+/// Sums the balances of the token accounts of each owner.
+/// Owners are sorted by descending balance, ties by owner.
+pub fn aggregate_by_owner(holders: &[TokenHolder]) -> Vec<OwnerBalance> {
+    let mut balances: HashMap<Pubkey, OwnerBalance> = HashMap::new();
+    for holder in holders {
+        let balance = balances
+            .entry(holder.owner)
+            .or_insert_with(|| OwnerBalance {
+                owner: holder.owner,
+                amount: 0,
+                token_accounts: 0,
+            });
+        balance.amount = balance.amount.saturating_add(holder.amount);
+        balance.token_accounts += 1;
+    }
+    let mut balances: Vec<OwnerBalance> = balances.into_values().collect();
+    balances.sort_unstable_by_key(|balance| (Reverse(balance.amount), balance.owner));
+    balances
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{append_vec::test_utils::TestAccount, test_utils::TestExtractor},
+        solana_sdk::{
+            account::{AccountSharedData, WritableAccount},
+            program_option::COption,
+            program_pack::Pack,
+        },
+        spl_token_2022::state::{Account, AccountState},
+    };
+
+    fn token_account(mint: Pubkey, owner: Pubkey, amount: u64, program: Pubkey) -> TestAccount {
+        let token = Account {
+            mint,
+            owner,
+            amount,
+            state: AccountState::Initialized,
+            delegate: COption::Some(owner),
+            delegated_amount: amount / 2,
+            ..Account::default()
+        };
+        let mut data = vec![0; Account::LEN];
+        Account::pack(token, &mut data).unwrap();
+        TestAccount::new(
+            Pubkey::new_unique(),
+            AccountSharedData::create(1, data, program, false, 0),
+        )
+    }
+
+    #[test]
+    fn lists_holders_by_balance() {
+        let mint = Pubkey::new_unique();
+        let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let accounts = vec![
+            token_account(mint, first, 10, spl_token::id()),
+            token_account(mint, second, 0, spl_token::id()),
+            token_account(mint, second, 30, spl_token_2022::id()),
+            token_account(mint, first, 10, spl_token_2022::id()),
+            token_account(Pubkey::new_unique(), first, 100, spl_token::id()),
+        ];
+        let pubkeys: Vec<_> = accounts.iter().map(|account| account.pubkey).collect();
+        let mut extractor = TestExtractor::new(vec![(1, 1, accounts)]);
+        let accounts = LatestAccounts::from_extractor(&mut extractor).unwrap();
+
+        let holders = token_holders(&accounts, &mint, false);
+        let mut tied = [pubkeys[0], pubkeys[3]];
+        tied.sort();
+        assert_eq!(
+            holders
+                .iter()
+                .map(|holder| (holder.token_account, holder.amount))
+                .collect::<Vec<_>>(),
+            vec![
+                (pubkeys[2], 30),
+                (tied[0], 10),
+                (tied[1], 10),
+                (pubkeys[1], 0)
+            ]
+        );
+        assert_eq!(
+            holders[0],
+            TokenHolder {
+                owner: second,
+                token_account: pubkeys[2],
+                amount: 30,
+                delegate: Some(second),
+                delegated_amount: 15,
+            }
+        );
+
+        let non_empty = token_holders(&accounts, &mint, true);
+        assert_eq!(non_empty[..], holders[..3]);
+
+        assert_eq!(
+            aggregate_by_owner(&holders),
+            vec![
+                OwnerBalance {
+                    owner: second,
+                    amount: 30,
+                    token_accounts: 2,
+                },
+                OwnerBalance {
+                    owner: first,
+                    amount: 20,
+                    token_accounts: 2,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn aggregates_saturating_balances() {
+        let mut owners = [Pubkey::new_unique(), Pubkey::new_unique()];
+        owners.sort();
+        let holder = |owner, amount| TokenHolder {
+            owner,
+            token_account: Pubkey::new_unique(),
+            amount,
+            delegate: None,
+            delegated_amount: 0,
+        };
+        let holders = [
+            holder(owners[1], 5),
+            holder(owners[0], 2),
+            holder(owners[0], 3),
+            holder(owners[1], u64::MAX),
+        ];
+        assert_eq!(
+            aggregate_by_owner(&holders),
+            vec![
+                OwnerBalance {
+                    owner: owners[1],
+                    amount: u64::MAX,
+                    token_accounts: 2,
+                },
+                OwnerBalance {
+                    owner: owners[0],
+                    amount: 5,
+                    token_accounts: 2,
+                },
+            ]
+        );
+    }
+}
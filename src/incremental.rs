@@ -0,0 +1,231 @@
+use {
+    crate::{
+        archived::ArchiveSnapshotExtractor, AccountsDbFields, AppendVecIterator,
+        DeserializableVersionedBank, ExtraFieldsToDeserialize, SerializableAccountStorageEntry,
+        SnapshotError, SnapshotExtractor, SnapshotResult,
+    },
+    log::info,
+    solana_runtime::snapshot_archive_info::{
+        IncrementalSnapshotArchiveInfo, SnapshotArchiveInfoGetter,
+    },
+    solana_sdk::clock::Slot,
+    std::{fs::File, io::Read, path::Path},
+};
+
+/// Extracts account data from a full snapshot archive and an incremental snapshot archive
+/// built on top of it.
+///
+/// AppendVecs of the full snapshot are yielded first, followed by the AppendVecs of the
+/// incremental snapshot, so later copies of an account always come from newer slots.
+pub struct IncrementalSnapshotExtractor<Source>
+where
+    Source: Read + Unpin + 'static,
+{
+    full: ArchiveSnapshotExtractor<Source>,
+    incremental: ArchiveSnapshotExtractor<Source>,
+    base_slot: Slot,
+}
+
+impl<Source> SnapshotExtractor for IncrementalSnapshotExtractor<Source>
+where
+    Source: Read + Unpin + 'static,
+{
+    fn iter(&mut self) -> AppendVecIterator<'_> {
+        Box::new(self.full.iter().chain(self.incremental.iter()))
+    }
+
+    /// Bank fields of the incremental snapshot.
+    fn bank_fields(&self) -> &DeserializableVersionedBank {
+        self.incremental.bank_fields()
+    }
+
+    /// AccountsDB fields of the incremental snapshot. These only list the AppendVecs stored in
+    /// the incremental archive; use [`Self::full`] for the ones of the full snapshot.
+    fn accounts_db_fields(&self) -> &AccountsDbFields<SerializableAccountStorageEntry> {
+        self.incremental.accounts_db_fields()
+    }
+
+    /// Extra fields of the incremental snapshot, including its incremental snapshot persistence.
+    fn extra_fields(&self) -> &ExtraFieldsToDeserialize {
+        self.incremental.extra_fields()
+    }
+}
+
+impl<Source> IncrementalSnapshotExtractor<Source>
+where
+    Source: Read + Unpin + 'static,
+{
+    /// Combines a full snapshot with an incremental snapshot taken relative to `base_slot`.
+    ///
+    /// `base_slot` has to be the full snapshot slot recorded in the incremental snapshot
+    /// manifest, the slot of `full`, and before the slot of `incremental`.
+    pub fn from_extractors(
+        full: ArchiveSnapshotExtractor<Source>,
+        incremental: ArchiveSnapshotExtractor<Source>,
+        base_slot: Slot,
+    ) -> SnapshotResult<Self> {
+        let manifest_base_slot = incremental
+            .extra_fields()
+            .incremental_snapshot_persistence
+            .as_ref()
+            .map(|persistence| persistence.full_slot)
+            .ok_or(SnapshotError::NotIncrementalSnapshot(incremental.slot()))?;
+        if manifest_base_slot != base_slot {
+            return Err(SnapshotError::ManifestBaseSlotMismatch {
+                base_slot,
+                manifest_base_slot,
+            });
+        }
+        if incremental.slot() <= base_slot {
+            return Err(SnapshotError::IncrementalSlotNotAfterBase {
+                slot: incremental.slot(),
+                base_slot,
+            });
+        }
+        if full.slot() != base_slot {
+            return Err(SnapshotError::BaseSlotMismatch {
+                base_slot,
+                full_slot: full.slot(),
+            });
+        }
+        info!(
+            "Combining full snapshot at slot {} with incremental snapshot at slot {}",
+            base_slot,
+            incremental.slot()
+        );
+        Ok(IncrementalSnapshotExtractor {
+            full,
+            incremental,
+            base_slot,
+        })
+    }
+
+    pub const fn full(&self) -> &ArchiveSnapshotExtractor<Source> {
+        &self.full
+    }
+
+    pub const fn incremental(&self) -> &ArchiveSnapshotExtractor<Source> {
+        &self.incremental
+    }
+
+    /// Slot of the full snapshot the incremental snapshot is based on.
+    pub const fn base_slot(&self) -> Slot {
+        self.base_slot
+    }
+
+    /// Slot of the incremental snapshot, i.e. the slot of the combined account state.
+    pub const fn slot(&self) -> Slot {
+        self.incremental.slot()
+    }
+}
+
+impl IncrementalSnapshotExtractor<File> {
+    /// Opens a full snapshot archive together with an
+    /// `incremental-snapshot-<base>-<slot>-<hash>.tar.zst` archive.
+    pub fn open(full_path: &Path, incremental_path: &Path) -> SnapshotResult<Self> {
+        let incremental_info =
+            IncrementalSnapshotArchiveInfo::new_from_path(incremental_path.to_path_buf())
+                .map_err(|err| SnapshotError::InvalidArchiveName(err.to_string()))?;
+
+        let full = ArchiveSnapshotExtractor::open(full_path)?;
+        let incremental = ArchiveSnapshotExtractor::open(incremental_path)?;
+        if incremental.slot() != incremental_info.slot() {
+            return Err(SnapshotError::SlotMismatch {
+                expected: incremental_info.slot(),
+                actual: incremental.slot(),
+            });
+        }
+
+        Self::from_extractors(full, incremental, incremental_info.base_slot())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{archived::test_utils::*, latest::LatestAccounts},
+        solana_runtime::snapshot_utils::ArchiveFormat,
+        solana_sdk::{
+            account::{AccountSharedData, ReadableAccount},
+            pubkey::Pubkey,
+        },
+    };
+
+    const FORMAT: ArchiveFormat = ArchiveFormat::Tar;
+
+    #[test]
+    fn combines_full_and_incremental_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let (updated, created) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let owner = Pubkey::new_unique();
+        let bank = new_bank();
+        bank.store_account(&updated, &AccountSharedData::new(10, 0, &owner));
+        let full_path = write_full_snapshot_archive(&bank, dir.path(), FORMAT);
+        let bank = new_child_bank(bank, 1);
+        bank.store_account(&updated, &AccountSharedData::new(20, 0, &owner));
+        bank.store_account(&created, &AccountSharedData::new(30, 0, &owner));
+        let incremental_path = write_incremental_snapshot_archive(&bank, 0, dir.path(), FORMAT);
+
+        let mut extractor =
+            IncrementalSnapshotExtractor::open(&full_path, &incremental_path).unwrap();
+        assert_eq!((extractor.base_slot(), extractor.slot()), (0, 1));
+        assert_eq!(extractor.full().slot(), 0);
+        assert_eq!(extractor.bank_fields().slot, 1);
+
+        let accounts = LatestAccounts::from_extractor(&mut extractor).unwrap();
+        assert_eq!(accounts.get(&updated).unwrap().lamports(), 20);
+        assert_eq!(accounts.slot_of(&updated), Some(1));
+        assert_eq!(accounts.get(&created).unwrap().lamports(), 30);
+    }
+
+    #[test]
+    fn rejects_mismatched_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+        let bank = new_bank();
+        let full_0 = write_full_snapshot_archive(&bank, &dir.path().join("0"), FORMAT);
+        let bank = new_child_bank(bank, 1);
+        let full_1 = write_full_snapshot_archive(&bank, &dir.path().join("1"), FORMAT);
+        let bank = new_child_bank(bank, 2);
+        bank.store_account(
+            &Pubkey::new_unique(),
+            &AccountSharedData::new(1, 0, &Pubkey::new_unique()),
+        );
+        // based on the full snapshot at slot 0, which the accounts db of the bank knows
+        let incremental_0_2 =
+            write_incremental_snapshot_archive(&bank, 0, &dir.path().join("2"), FORMAT);
+        let open = |path: &Path| ArchiveSnapshotExtractor::open(path).unwrap();
+
+        // the right pairing
+        IncrementalSnapshotExtractor::from_extractors(open(&full_0), open(&incremental_0_2), 0)
+            .unwrap();
+
+        // a full snapshot of another slot than the incremental snapshot is based on
+        assert!(matches!(
+            IncrementalSnapshotExtractor::from_extractors(open(&full_1), open(&incremental_0_2), 1)
+                .err()
+                .unwrap(),
+            SnapshotError::ManifestBaseSlotMismatch {
+                base_slot: 1,
+                manifest_base_slot: 0
+            }
+        ));
+        assert!(matches!(
+            IncrementalSnapshotExtractor::from_extractors(open(&full_1), open(&incremental_0_2), 0)
+                .err()
+                .unwrap(),
+            SnapshotError::BaseSlotMismatch {
+                base_slot: 0,
+                full_slot: 1
+            }
+        ));
+
+        // a full snapshot in place of the incremental one
+        assert!(matches!(
+            IncrementalSnapshotExtractor::from_extractors(open(&full_0), open(&full_1), 0)
+                .err()
+                .unwrap(),
+            SnapshotError::NotIncrementalSnapshot(1)
+        ));
+    }
+}
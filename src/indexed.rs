@@ -0,0 +1,371 @@
+use {
+    crate::{
+        append_vec::AppendVec,
+        append_vec::StoredAccountMeta,
+        append_vec_iter,
+        filter::{AccountFilters, MemcmpFilter},
+        index::{IndexEntry, PubkeyIndex},
+        secondary_index::SecondaryIndex,
+        token::TokenProgram,
+        unpacked::UnpackedSnapshotExtractor,
+        NoopReadProgressTracking, SnapshotError, SnapshotExtractor, SnapshotResult,
+    },
+    solana_accounts_db::accounts_index::AccountIndex,
+    solana_sdk::{account::AccountSharedData, clock::Slot, pubkey::Pubkey},
+    std::{
+        collections::HashMap,
+        path::Path,
+        sync::{Arc, Mutex},
+    },
+};
+
+/// How many accounts files [`IndexedAccounts`] keeps open for lookups.
+pub const ACCOUNTS_FILE_CACHE_CAPACITY: usize = 1024;
+
+/// Looks up accounts of an unpacked snapshot by pubkey, without scanning the snapshot.
+///
+/// The pubkey index points to the accounts file of the latest copy of each account. Accounts
+/// files are only opened and mapped when an account in them is requested, and the
+/// [`ACCOUNTS_FILE_CACHE_CAPACITY`] most recently used ones stay open. Queries by owner, token
+/// mint or token owner use the secondary indexes that are opened, and otherwise read all
+/// accounts files once.
+pub struct IndexedAccounts {
+    extractor: UnpackedSnapshotExtractor,
+    index: PubkeyIndex,
+    secondary_indexes: HashMap<AccountIndex, SecondaryIndex>,
+    accounts_files: Mutex<AccountsFileCache>,
+}
+
+/// The stored copy of an account, keeping its accounts file open.
+pub struct IndexedAccount {
+    accounts_file: Arc<AppendVec>,
+    slot: Slot,
+    offset: usize,
+}
+
+impl IndexedAccount {
+    pub fn access(&self) -> Option<StoredAccountMeta<'_>> {
+        Some(self.accounts_file.get_account(self.offset)?.0)
+    }
+
+    pub const fn slot(&self) -> Slot {
+        self.slot
+    }
+}
+
+impl IndexedAccounts {
+    /// Uses an index built from the same snapshot, see [`PubkeyIndex::build`].
+    pub fn new(extractor: UnpackedSnapshotExtractor, index: PubkeyIndex) -> SnapshotResult<Self> {
+        let slot = extractor.bank_fields().slot;
+        if index.slot() != slot {
+            return Err(SnapshotError::SlotMismatch {
+                expected: slot,
+                actual: index.slot(),
+            });
+        }
+        Ok(IndexedAccounts {
+            extractor,
+            index,
+            secondary_indexes: HashMap::new(),
+            accounts_files: Mutex::new(AccountsFileCache::new(ACCOUNTS_FILE_CACHE_CAPACITY)),
+        })
+    }
+
+    /// Opens the unpacked snapshot at `path` with the index at `index_path`, building the index
+    /// first if the file does not exist.
+    pub fn open(path: &Path, index_path: &Path) -> SnapshotResult<Self> {
+        let extractor =
+            UnpackedSnapshotExtractor::open(path, Box::new(NoopReadProgressTracking {}))?;
+        Self::from_extractor(extractor, index_path)
+    }
+
+    /// Uses the index at `index_path` for the opened unpacked snapshot, building the index first
+    /// if the file does not exist.
+    pub fn from_extractor(
+        mut extractor: UnpackedSnapshotExtractor,
+        index_path: &Path,
+    ) -> SnapshotResult<Self> {
+        let index = if index_path.is_file() {
+            PubkeyIndex::open(index_path)?
+        } else {
+            PubkeyIndex::build(&mut extractor, index_path)?
+        };
+        Self::new(extractor, index)
+    }
+
+    pub const fn extractor(&self) -> &UnpackedSnapshotExtractor {
+        &self.extractor
+    }
+
+    pub const fn index(&self) -> &PubkeyIndex {
+        &self.index
+    }
+
+    /// Opens the secondary indexes of `kinds` stored next to the index at `index_path`,
+    /// building those whose file does not exist yet.
+    pub fn open_secondary_indexes(
+        &mut self,
+        index_path: &Path,
+        kinds: &[AccountIndex],
+    ) -> SnapshotResult<()> {
+        let mut missing = Vec::new();
+        for kind in kinds {
+            let path = SecondaryIndex::path(index_path, kind);
+            if !path.is_file() {
+                missing.push(kind.clone());
+                continue;
+            }
+            let secondary_index = SecondaryIndex::open(&path)?;
+            if secondary_index.slot() != self.index.slot() {
+                return Err(SnapshotError::SlotMismatch {
+                    expected: self.index.slot(),
+                    actual: secondary_index.slot(),
+                });
+            }
+            if secondary_index.kind() != kind {
+                return Err(SnapshotError::InvalidIndex(format!(
+                    "{} is not a {:?} index",
+                    path.display(),
+                    kind
+                )));
+            }
+            self.secondary_indexes.insert(kind.clone(), secondary_index);
+        }
+        if !missing.is_empty() {
+            for secondary_index in
+                SecondaryIndex::build(&mut self.extractor, &self.index, index_path, &missing)?
+            {
+                self.secondary_indexes
+                    .insert(secondary_index.kind().clone(), secondary_index);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn secondary_index(&self, kind: &AccountIndex) -> Option<&SecondaryIndex> {
+        self.secondary_indexes.get(kind)
+    }
+
+    /// Returns the stored copy of the account, if it exists with a non-zero balance.
+    pub fn get_stored_account(&self, pubkey: &Pubkey) -> SnapshotResult<Option<IndexedAccount>> {
+        self.index
+            .get(pubkey)
+            .map(|entry| self.open_entry(&entry))
+            .transpose()
+    }
+
+    /// Returns a copy of the account, if it exists with a non-zero balance.
+    pub fn get_account(&self, pubkey: &Pubkey) -> SnapshotResult<Option<AccountSharedData>> {
+        Ok(self
+            .get_stored_account(pubkey)?
+            .and_then(|account| Some(account.access()?.clone_account())))
+    }
+
+    /// Returns copies of the accounts in the order of `pubkeys`.
+    pub fn get_multiple_accounts(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> SnapshotResult<Vec<Option<AccountSharedData>>> {
+        pubkeys
+            .iter()
+            .map(|pubkey| self.get_account(pubkey))
+            .collect()
+    }
+
+    /// Returns copies of the accounts owned by `program_id` matching the filters, like the RPC
+    /// `getProgramAccounts`, sorted by pubkey.
+    pub fn get_program_accounts(
+        &self,
+        program_id: &Pubkey,
+        filters: &AccountFilters,
+    ) -> SnapshotResult<Vec<(Pubkey, AccountSharedData)>> {
+        let filters = filters.clone().with_owner(*program_id);
+        self.get_filtered_accounts(&AccountIndex::ProgramId, program_id, &filters)
+    }
+
+    /// Returns copies of the token accounts of `owner`, of the token program and token-2022.
+    pub fn get_token_accounts_by_owner(
+        &self,
+        owner: &Pubkey,
+    ) -> SnapshotResult<Vec<(Pubkey, AccountSharedData)>> {
+        self.get_token_accounts(&AccountIndex::SplTokenOwner, owner, 32)
+    }
+
+    /// Returns copies of the token accounts of `mint`, of the token program and token-2022.
+    pub fn get_token_accounts_by_mint(
+        &self,
+        mint: &Pubkey,
+    ) -> SnapshotResult<Vec<(Pubkey, AccountSharedData)>> {
+        self.get_token_accounts(&AccountIndex::SplTokenMint, mint, 0)
+    }
+
+    fn get_token_accounts(
+        &self,
+        kind: &AccountIndex,
+        key: &Pubkey,
+        offset: usize,
+    ) -> SnapshotResult<Vec<(Pubkey, AccountSharedData)>> {
+        if self.secondary_indexes.contains_key(kind) {
+            return self.get_filtered_accounts(kind, key, &AccountFilters::new());
+        }
+        // without the index, find the token accounts of both programs in one scan
+        let memcmp = MemcmpFilter::new(offset, key.to_bytes().to_vec());
+        let programs = [TokenProgram::Token.id(), TokenProgram::Token2022.id()];
+        self.scan(|stored| {
+            programs.contains(&stored.account_meta.owner)
+                && memcmp.bytes_match(stored.data)
+                && SecondaryIndex::key(kind, stored).as_ref() == Some(key)
+        })
+    }
+
+    /// Looks up the candidates in the secondary index of `kind` if it is open, and otherwise
+    /// scans all accounts files.
+    fn get_filtered_accounts(
+        &self,
+        kind: &AccountIndex,
+        key: &Pubkey,
+        filters: &AccountFilters,
+    ) -> SnapshotResult<Vec<(Pubkey, AccountSharedData)>> {
+        let Some(secondary_index) = self.secondary_indexes.get(kind) else {
+            return self.scan(|stored| filters.matches(stored));
+        };
+        let mut accounts = Vec::new();
+        for pubkey in secondary_index.get(key) {
+            let Some(entry) = self.index.get(&pubkey) else {
+                continue;
+            };
+            let account = self.open_entry(&entry)?;
+            let Some(stored) = account.access() else {
+                continue;
+            };
+            if filters.matches(&stored) {
+                accounts.push((pubkey, stored.clone_account()));
+            }
+        }
+        Ok(accounts)
+    }
+
+    /// Copies the latest accounts for which `keep` returns true, sorted by pubkey. Reads each
+    /// accounts file once, in storage order, without keeping it open.
+    fn scan(
+        &self,
+        mut keep: impl FnMut(&StoredAccountMeta) -> bool,
+    ) -> SnapshotResult<Vec<(Pubkey, AccountSharedData)>> {
+        let mut accounts = Vec::new();
+        for accounts_file in self.extractor.unboxed_iter() {
+            let accounts_file = accounts_file?;
+            for handle in append_vec_iter(&accounts_file) {
+                let Some(stored) = handle.access() else {
+                    continue;
+                };
+                let entry = IndexEntry {
+                    slot: accounts_file.slot(),
+                    append_vec_id: accounts_file.id(),
+                    offset: stored.offset as u64,
+                };
+                // only the latest copy the index points to
+                if self.index.get(&stored.meta.pubkey) == Some(entry) && keep(&stored) {
+                    accounts.push((stored.meta.pubkey, stored.clone_account()));
+                }
+            }
+        }
+        accounts.sort_unstable_by_key(|(pubkey, _)| *pubkey);
+        Ok(accounts)
+    }
+
+    fn open_entry(&self, entry: &IndexEntry) -> SnapshotResult<IndexedAccount> {
+        let accounts_file = self.accounts_files.lock().unwrap().get_or_open(
+            (entry.slot, entry.append_vec_id),
+            || {
+                self.extractor
+                    .open_accounts_file(entry.slot, entry.append_vec_id)
+            },
+        )?;
+        Ok(IndexedAccount {
+            accounts_file,
+            slot: entry.slot,
+            offset: entry.offset as usize,
+        })
+    }
+}
+
+/// Accounts files opened for lookups, closing the least recently used one beyond `capacity`.
+struct AccountsFileCache {
+    capacity: usize,
+    /// the file and when it was last used
+    files: HashMap<(Slot, u64), (Arc<AppendVec>, u64)>,
+    uses: u64,
+}
+
+impl AccountsFileCache {
+    fn new(capacity: usize) -> Self {
+        AccountsFileCache {
+            capacity,
+            files: HashMap::new(),
+            uses: 0,
+        }
+    }
+
+    fn get_or_open(
+        &mut self,
+        key: (Slot, u64),
+        open: impl FnOnce() -> SnapshotResult<AppendVec>,
+    ) -> SnapshotResult<Arc<AppendVec>> {
+        self.uses += 1;
+        if let Some((accounts_file, last_used)) = self.files.get_mut(&key) {
+            *last_used = self.uses;
+            return Ok(Arc::clone(accounts_file));
+        }
+        let accounts_file = Arc::new(open()?);
+        if self.files.len() >= self.capacity {
+            let least_recently_used = self
+                .files
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| *key);
+            if let Some(least_recently_used) = least_recently_used {
+                self.files.remove(&least_recently_used);
+            }
+        }
+        self.files
+            .insert(key, (Arc::clone(&accounts_file), self.uses));
+        Ok(accounts_file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(opened: &mut Vec<(Slot, u64)>, key: (Slot, u64)) -> SnapshotResult<AppendVec> {
+        opened.push(key);
+        Ok(AppendVec::new_from_reader(
+            &mut [0u8; 64].as_slice(),
+            64,
+            key.0,
+            key.1,
+        )?)
+    }
+
+    #[test]
+    fn cache_closes_least_recently_used_file() {
+        let mut cache = AccountsFileCache::new(2);
+        let mut opened = Vec::new();
+        for key in [(1, 1), (2, 2), (1, 1), (3, 3), (1, 1), (2, 2)] {
+            let accounts_file = cache.get_or_open(key, || open(&mut opened, key)).unwrap();
+            assert_eq!((accounts_file.slot(), accounts_file.id()), key);
+            assert!(cache.files.len() <= 2);
+        }
+        // (2, 2) was closed for (3, 3), as (1, 1) was used more recently
+        assert_eq!(opened, vec![(1, 1), (2, 2), (3, 3), (2, 2)]);
+    }
+
+    #[test]
+    fn cache_keeps_nothing_on_open_error() {
+        let mut cache = AccountsFileCache::new(2);
+        assert!(cache
+            .get_or_open((1, 1), || Err(SnapshotError::NoSnapshotManifest))
+            .is_err());
+        assert!(cache.files.is_empty());
+    }
+}
@@ -0,0 +1,413 @@
+use {
+    crate::{
+        append_vec::{AppendVec, StoredAccountMeta},
+        append_vec_iter,
+        filter::AccountFilters,
+        AppendVecIterator, SnapshotError, SnapshotExtractor, SnapshotResult,
+        StoredAccountMetaHandle,
+    },
+    log::info,
+    solana_sdk::{clock::Slot, pubkey::Pubkey},
+    std::{collections::HashMap, time::Instant},
+};
+
+/// `(slot, write_version, append_vec_id, offset)` of a stored copy, greatest for the latest one.
+pub(crate) type VersionKey = (Slot, u64, u64, usize);
+
+/// Position of a stored copy of an account within the accounts files of a snapshot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccountPosition {
+    pub slot: Slot,
+    pub append_vec_id: u64,
+    pub offset: usize,
+}
+
+/// A stored copy of an account and what decides whether it is the latest one.
+#[derive(Clone, Copy, Debug)]
+struct AccountVersion {
+    position: AccountPosition,
+    write_version: u64,
+    has_lamports: bool,
+}
+
+impl AccountPosition {
+    /// Orders copies by `(slot, write_version)`. Ties go to the copy in the accounts file with
+    /// the higher id and then to the copy stored later, as stores within a file are ordered, so
+    /// the result does not depend on the order the files are read in.
+    pub(crate) const fn version_key(&self, write_version: u64) -> VersionKey {
+        (self.slot, write_version, self.append_vec_id, self.offset)
+    }
+}
+
+impl AccountVersion {
+    const fn key(&self) -> VersionKey {
+        self.position.version_key(self.write_version)
+    }
+}
+
+/// The position of the latest copy of every account in a snapshot, resolved in one pass over
+/// the accounts files without keeping them.
+///
+/// Only a position per pubkey is held, about 100 bytes per account, so the accounts themselves
+/// can be streamed in a second pass with [`LatestPositions::for_each_account`]. An archive can
+/// only be read once, so the second pass needs a freshly opened extractor.
+#[derive(Default)]
+pub struct LatestPositions {
+    latest: HashMap<Pubkey, AccountVersion>,
+    stored_accounts: u64,
+}
+
+impl LatestPositions {
+    pub fn resolve(extractor: &mut impl SnapshotExtractor) -> SnapshotResult<Self> {
+        Self::resolve_with(extractor, |_, _| {})
+    }
+
+    /// Like [`LatestPositions::resolve`], also calling `visit` with every stored copy of an
+    /// account, superseded or not, so more can be collected in the same pass.
+    pub fn resolve_with(
+        extractor: &mut impl SnapshotExtractor,
+        mut visit: impl FnMut(AccountPosition, &StoredAccountMeta<'_>),
+    ) -> SnapshotResult<Self> {
+        let started = Instant::now();
+        let mut positions = Self::default();
+        for append_vec in extractor.iter() {
+            positions.add(&append_vec?, &mut visit);
+        }
+        positions.finish(started);
+        Ok(positions)
+    }
+
+    fn add(
+        &mut self,
+        append_vec: &AppendVec,
+        mut visit: impl FnMut(AccountPosition, &StoredAccountMeta<'_>),
+    ) {
+        for handle in append_vec_iter(append_vec) {
+            let Some(stored) = handle.access() else {
+                continue;
+            };
+            self.stored_accounts += 1;
+            let version = AccountVersion {
+                position: AccountPosition {
+                    slot: append_vec.slot(),
+                    append_vec_id: append_vec.id(),
+                    offset: stored.offset,
+                },
+                write_version: stored.meta.write_version_obsolete,
+                has_lamports: stored.account_meta.lamports != 0,
+            };
+            visit(version.position, &stored);
+            self.latest
+                .entry(stored.meta.pubkey)
+                .and_modify(|current| {
+                    if version.key() > current.key() {
+                        *current = version;
+                    }
+                })
+                .or_insert(version);
+        }
+    }
+
+    /// Drops the accounts ending with zero lamports.
+    fn finish(&mut self, started: Instant) {
+        let unique_accounts = self.latest.len();
+        self.latest.retain(|_, version| version.has_lamports);
+        info!(
+            "Resolved {} accounts from {} unique pubkeys and {} stored copies in {:?}",
+            self.latest.len(),
+            unique_accounts,
+            self.stored_accounts,
+            started.elapsed()
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.latest.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_empty()
+    }
+
+    /// Position of the latest copy of the account, if it exists with a non-zero balance.
+    pub fn get(&self, pubkey: &Pubkey) -> Option<AccountPosition> {
+        self.latest.get(pubkey).map(|version| version.position)
+    }
+
+    /// Iterates over the position of the latest copy of each account, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Pubkey, AccountPosition)> {
+        self.latest
+            .iter()
+            .map(|(pubkey, version)| (pubkey, version.position))
+    }
+
+    /// Calls `callback` with the latest copy of each account and the slot it was stored in,
+    /// reading the accounts files of `extractor` one at a time. The extractor has to read the
+    /// same snapshot the positions were resolved from. Returns the number of accounts visited.
+    pub fn for_each_account<E>(
+        &self,
+        extractor: &mut impl SnapshotExtractor,
+        mut callback: impl FnMut(Slot, &StoredAccountMeta<'_>) -> Result<(), E>,
+    ) -> Result<u64, E>
+    where
+        E: From<SnapshotError>,
+    {
+        let mut visited = 0;
+        for append_vec in extractor.iter() {
+            let append_vec = append_vec?;
+            for handle in append_vec_iter(&append_vec) {
+                let Some(stored) = handle.access() else {
+                    continue;
+                };
+                let position = AccountPosition {
+                    slot: append_vec.slot(),
+                    append_vec_id: append_vec.id(),
+                    offset: stored.offset,
+                };
+                if self.get(&stored.meta.pubkey) == Some(position) {
+                    callback(position.slot, &stored)?;
+                    visited += 1;
+                }
+            }
+        }
+        Ok(visited)
+    }
+}
+
+/// The final state of all accounts in a snapshot, with random access to each account.
+///
+/// Only the copy with the highest `(slot, write_version)` of each pubkey is kept and accounts
+/// ending with zero lamports are dropped, leaving the account set the validator would load.
+/// Ties are broken as in [`LatestPositions`].
+///
+/// All accounts files read from the snapshot stay mapped, as the accounts point into them.
+/// The files of an unpacked snapshot are mapped from disk, but those of an archive are
+/// decompressed into anonymous memory, so the whole snapshot is held in RAM. Use
+/// [`LatestPositions`] to stream the accounts of large archives instead.
+pub struct LatestAccounts {
+    append_vecs: Vec<AppendVec>,
+    /// index into `append_vecs` by slot and id
+    files: HashMap<(Slot, u64), usize>,
+    positions: LatestPositions,
+}
+
+impl LatestAccounts {
+    pub fn from_extractor(extractor: &mut impl SnapshotExtractor) -> SnapshotResult<Self> {
+        Self::from_append_vecs(extractor.iter())
+    }
+
+    pub fn from_append_vecs(iterator: AppendVecIterator<'_>) -> SnapshotResult<Self> {
+        let started = Instant::now();
+        let mut append_vecs = Vec::new();
+        let mut files = HashMap::new();
+        let mut positions = LatestPositions::default();
+        for append_vec in iterator {
+            let append_vec = append_vec?;
+            positions.add(&append_vec, |_, _| {});
+            files.insert((append_vec.slot(), append_vec.id()), append_vecs.len());
+            append_vecs.push(append_vec);
+        }
+        positions.finish(started);
+
+        Ok(LatestAccounts {
+            append_vecs,
+            files,
+            positions,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// All accounts files read from the snapshot, including superseded account copies.
+    pub fn append_vecs(&self) -> &[AppendVec] {
+        self.append_vecs.as_slice()
+    }
+
+    /// Returns the latest copy of the account, if it exists with a non-zero balance.
+    pub fn get(&self, pubkey: &Pubkey) -> Option<StoredAccountMeta<'_>> {
+        self.positions
+            .get(pubkey)
+            .and_then(|position| self.handle(&position).access())
+    }
+
+    /// Slot at which the latest copy of the account was stored.
+    pub fn slot_of(&self, pubkey: &Pubkey) -> Option<Slot> {
+        self.positions.get(pubkey).map(|position| position.slot)
+    }
+
+    /// Iterates over the latest copy of each account, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (Slot, StoredAccountMetaHandle<'_>)> {
+        self.positions
+            .iter()
+            .map(|(_, position)| (position.slot, self.handle(&position)))
+    }
+
+    /// Iterates over the latest copy of each account matching `filters`, in no particular order.
+    pub fn filter<'a>(
+        &'a self,
+        filters: &'a AccountFilters,
+    ) -> impl Iterator<Item = (Slot, StoredAccountMeta<'a>)> + 'a {
+        self.iter().filter_map(|(slot, handle)| {
+            handle
+                .access()
+                .filter(|stored| filters.matches(stored))
+                .map(|stored| (slot, stored))
+        })
+    }
+
+    fn handle(&self, position: &AccountPosition) -> StoredAccountMetaHandle<'_> {
+        let file = self.files[&(position.slot, position.append_vec_id)];
+        StoredAccountMetaHandle::new(&self.append_vecs[file], position.offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{append_vec::test_utils::TestAccount, test_utils::TestExtractor},
+        solana_sdk::{
+            account::{AccountSharedData, ReadableAccount},
+            hash::Hash,
+        },
+    };
+
+    fn account(pubkey: Pubkey, lamports: u64, write_version: u64) -> TestAccount {
+        TestAccount {
+            pubkey,
+            write_version,
+            hash: Hash::default(),
+            account: AccountSharedData::new(lamports, 0, &Pubkey::default()),
+        }
+    }
+
+    /// Resolves the files in the given and in reverse order, which have to agree.
+    fn resolve(files: Vec<(Slot, u64, Vec<TestAccount>)>) -> LatestPositions {
+        let mut extractor = TestExtractor::new(files);
+        let positions = LatestPositions::resolve(&mut extractor).unwrap();
+        extractor.files.reverse();
+        let reversed = LatestPositions::resolve(&mut extractor).unwrap();
+        assert_eq!(
+            positions.iter().collect::<HashMap<_, _>>(),
+            reversed.iter().collect::<HashMap<_, _>>()
+        );
+        positions
+    }
+
+    /// Offset of the `index`th account of a file of accounts without data.
+    const fn offset(index: usize) -> usize {
+        // StoredMeta, AccountMeta and hash
+        index * 136
+    }
+
+    #[test]
+    fn higher_write_version_wins_within_slot() {
+        let pubkey = Pubkey::new_unique();
+        let positions = resolve(vec![
+            (5, 1, vec![account(pubkey, 1, 20)]),
+            (5, 2, vec![account(pubkey, 2, 10)]),
+        ]);
+        assert_eq!(
+            positions.get(&pubkey),
+            Some(AccountPosition {
+                slot: 5,
+                append_vec_id: 1,
+                offset: 0
+            })
+        );
+    }
+
+    #[test]
+    fn later_slot_wins_over_higher_write_version() {
+        let pubkey = Pubkey::new_unique();
+        let positions = resolve(vec![
+            (6, 1, vec![account(pubkey, 1, 0)]),
+            (5, 2, vec![account(pubkey, 2, 100)]),
+        ]);
+        assert_eq!(positions.get(&pubkey).unwrap().slot, 6);
+    }
+
+    #[test]
+    fn ties_go_to_higher_file_id_and_later_offset() {
+        let (in_files, in_file) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let positions = resolve(vec![
+            (5, 3, vec![account(in_files, 1, 7)]),
+            (5, 4, vec![account(in_files, 2, 7)]),
+            (5, 1, vec![account(in_file, 1, 7), account(in_file, 2, 7)]),
+        ]);
+        assert_eq!(
+            positions.get(&in_files),
+            Some(AccountPosition {
+                slot: 5,
+                append_vec_id: 4,
+                offset: 0
+            })
+        );
+        assert_eq!(
+            positions.get(&in_file),
+            Some(AccountPosition {
+                slot: 5,
+                append_vec_id: 1,
+                offset: offset(1)
+            })
+        );
+    }
+
+    #[test]
+    fn drops_accounts_ending_with_zero_lamports() {
+        let (closed, reopened) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let positions = resolve(vec![
+            (5, 1, vec![account(closed, 10, 0), account(reopened, 0, 0)]),
+            (6, 2, vec![account(closed, 0, 0), account(reopened, 10, 0)]),
+        ]);
+        assert_eq!(positions.len(), 1);
+        assert_eq!(positions.get(&closed), None);
+        assert_eq!(positions.get(&reopened).unwrap().slot, 6);
+    }
+
+    #[test]
+    fn visits_latest_accounts() {
+        let (first, second, closed) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let files = vec![
+            (5, 1, vec![account(first, 1, 0), account(second, 2, 0)]),
+            (6, 2, vec![account(first, 3, 0), account(closed, 0, 0)]),
+        ];
+        let mut extractor = TestExtractor::new(files);
+        let positions = LatestPositions::resolve(&mut extractor).unwrap();
+
+        let mut visited = Vec::new();
+        let count = positions
+            .for_each_account(&mut extractor, |slot, stored| {
+                visited.push((slot, stored.meta.pubkey, stored.lamports()));
+                Ok::<_, SnapshotError>(())
+            })
+            .unwrap();
+        assert_eq!(count, 2);
+        visited.sort();
+        let mut expected = vec![(5, second, 2), (6, first, 3)];
+        expected.sort();
+        assert_eq!(visited, expected);
+
+        // errors of the callback stop the pass
+        let result = positions.for_each_account(&mut extractor, |_, _| {
+            Err(SnapshotError::NoSnapshotManifest)
+        });
+        assert!(matches!(result, Err(SnapshotError::NoSnapshotManifest)));
+
+        let accounts = LatestAccounts::from_extractor(&mut extractor).unwrap();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts.get(&first).unwrap().lamports(), 3);
+        assert_eq!(accounts.slot_of(&second), Some(5));
+        assert!(accounts.get(&closed).is_none());
+    }
+}
@@ -1,20 +1,46 @@
 use {
     crate::{
         append_vec::{AppendVec, StoredAccountMeta},
+        filter::AccountFilters,
         solana::{
             deserialize_from, AccountsDbFields, DeserializableVersionedBank,
-            SerializableAccountStorageEntry,
+            ExtraFieldsToDeserialize, SerializableAccountStorageEntry,
         },
+        sysvars::Sysvars,
     },
+    solana_runtime::epoch_stakes::EpochStakes,
     std::{ffi::OsStr, io::Read, path::Path, str::FromStr},
     thiserror::Error,
 };
 
+pub mod accounts_hash;
+pub mod anchor;
 pub mod append_vec;
 pub mod archived;
+pub mod config;
+pub mod decoder;
+pub mod filter;
+pub mod geyser;
+pub mod holders;
+pub mod incremental;
+pub mod index;
+pub mod indexed;
+pub mod latest;
+pub mod lookup_table;
+pub mod lt_hash;
+pub mod nonce;
 pub mod parallel;
+pub mod programs;
+pub mod proof;
+pub mod rpc;
+pub mod secondary_index;
 pub mod solana;
+pub mod stake;
+pub mod sysvars;
+pub mod tiered_storage;
+pub mod token;
 pub mod unpacked;
+pub mod verify;
 
 const SNAPSHOTS_DIR: &str = "snapshots";
 
@@ -32,6 +58,27 @@ pub enum SnapshotError {
     UnexpectedAppendVec,
     #[error("Failed to create read progress tracking: {0}")]
     ReadProgressTracking(String),
+    #[error("Invalid snapshot archive name: {0}")]
+    InvalidArchiveName(String),
+    #[error(
+        "Incremental snapshot base slot {base_slot} does not match full snapshot slot {full_slot}"
+    )]
+    BaseSlotMismatch { base_slot: u64, full_slot: u64 },
+    #[error("Snapshot at slot {0} has no incremental snapshot persistence, it is not incremental")]
+    NotIncrementalSnapshot(u64),
+    #[error(
+        "Incremental snapshot manifest has base slot {manifest_base_slot}, expected {base_slot}"
+    )]
+    ManifestBaseSlotMismatch {
+        base_slot: u64,
+        manifest_base_slot: u64,
+    },
+    #[error("Incremental snapshot slot {slot} is not after its base slot {base_slot}")]
+    IncrementalSlotNotAfterBase { slot: u64, base_slot: u64 },
+    #[error("Unexpected snapshot slot {actual}, expected {expected}")]
+    SlotMismatch { expected: u64, actual: u64 },
+    #[error("Invalid index file: {0}")]
+    InvalidIndex(String),
 }
 
 pub type SnapshotResult<T> = Result<T, SnapshotError>;
@@ -40,6 +87,43 @@ pub type AppendVecIterator<'a> = Box<dyn Iterator<Item = SnapshotResult<AppendVe
 
 pub trait SnapshotExtractor: Sized {
     fn iter(&mut self) -> AppendVecIterator<'_>;
+
+    /// Bank fields read from the snapshot manifest.
+    fn bank_fields(&self) -> &DeserializableVersionedBank;
+
+    /// AccountsDB fields read from the snapshot manifest.
+    fn accounts_db_fields(&self) -> &AccountsDbFields<SerializableAccountStorageEntry>;
+
+    /// Fields newer validators append to the snapshot manifest.
+    fn extra_fields(&self) -> &ExtraFieldsToDeserialize;
+
+    /// Stakes of the epoch the snapshot was taken in.
+    fn current_epoch_stakes(&self) -> Option<EpochStakes> {
+        let bank_fields = self.bank_fields();
+        self.extra_fields()
+            .epoch_stakes(bank_fields, bank_fields.epoch)
+    }
+
+    /// Calls `callback` with the slot of every stored account matching `filters`.
+    /// All stored copies are visited, use `LatestAccounts` for the final account state.
+    fn for_each_account(
+        &mut self,
+        filters: &AccountFilters,
+        mut callback: impl FnMut(u64, StoredAccountMeta<'_>),
+    ) -> SnapshotResult<()> {
+        for append_vec in self.iter() {
+            let append_vec = append_vec?;
+            for stored in filters.iter(&append_vec) {
+                callback(append_vec.slot(), stored);
+            }
+        }
+        Ok(())
+    }
+
+    /// Decodes the latest copy of the known sysvar accounts.
+    fn sysvars(&mut self) -> SnapshotResult<Sysvars> {
+        Sysvars::from_extractor(self)
+    }
 }
 
 fn parse_append_vec_name(name: &OsStr) -> Option<(u64, u64)> {
@@ -63,7 +147,7 @@ pub fn append_vec_iter(append_vec: &AppendVec) -> impl Iterator<Item = StoredAcc
         })
     })
     .take_while(|account| account.is_some())
-    .filter_map(|account| account)
+    .flatten()
 }
 
 pub struct StoredAccountMetaHandle<'a> {
@@ -72,11 +156,11 @@ pub struct StoredAccountMetaHandle<'a> {
 }
 
 impl<'a> StoredAccountMetaHandle<'a> {
-    pub const fn new(append_vec: &'a AppendVec, offset: usize) -> StoredAccountMetaHandle {
+    pub const fn new(append_vec: &'a AppendVec, offset: usize) -> StoredAccountMetaHandle<'a> {
         Self { append_vec, offset }
     }
 
-    pub fn access(&self) -> Option<StoredAccountMeta<'_>> {
+    pub fn access(&self) -> Option<StoredAccountMeta<'a>> {
         Some(self.append_vec.get_account(self.offset)?.0)
     }
 }
@@ -90,7 +174,7 @@ pub trait ReadProgressTracking {
     ) -> SnapshotResult<Box<dyn Read>>;
 }
 
-struct NoopReadProgressTracking {}
+pub struct NoopReadProgressTracking {}
 
 impl ReadProgressTracking for NoopReadProgressTracking {
     fn new_read_progress_tracker(
@@ -102,3 +186,51 @@ impl ReadProgressTracking for NoopReadProgressTracking {
         Ok(rd)
     }
 }
+
+/// Extractors over accounts files built in memory, for the tests of the modules reading snapshots.
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use {
+        super::*,
+        crate::append_vec::test_utils::{new_append_vec, TestAccount},
+        solana_sdk::clock::Slot,
+    };
+
+    /// Accounts files by slot and id, in the order they are read, and the manifest fields
+    /// following the AccountsDB fields. The bank and AccountsDB fields are not available.
+    pub(crate) struct TestExtractor {
+        pub files: Vec<(Slot, u64, Vec<TestAccount>)>,
+        pub extra_fields: ExtraFieldsToDeserialize,
+    }
+
+    impl TestExtractor {
+        pub(crate) fn new(files: Vec<(Slot, u64, Vec<TestAccount>)>) -> Self {
+            Self {
+                files,
+                extra_fields: ExtraFieldsToDeserialize::default(),
+            }
+        }
+    }
+
+    impl SnapshotExtractor for TestExtractor {
+        fn iter(&mut self) -> AppendVecIterator<'_> {
+            Box::new(
+                self.files
+                    .iter()
+                    .map(|(slot, id, accounts)| Ok(new_append_vec(*slot, *id, accounts))),
+            )
+        }
+
+        fn bank_fields(&self) -> &DeserializableVersionedBank {
+            unimplemented!("no bank fields in test snapshots")
+        }
+
+        fn accounts_db_fields(&self) -> &AccountsDbFields<SerializableAccountStorageEntry> {
+            unimplemented!("no AccountsDB fields in test snapshots")
+        }
+
+        fn extra_fields(&self) -> &ExtraFieldsToDeserialize {
+            &self.extra_fields
+        }
+    }
+}
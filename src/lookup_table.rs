@@ -0,0 +1,130 @@
+use {
+    crate::latest::LatestAccounts,
+    solana_sdk::{
+        account::ReadableAccount,
+        address_lookup_table::{self, state::AddressLookupTable},
+        pubkey::Pubkey,
+    },
+};
+
+/// Decodes an initialized address lookup table. The address list borrows from the account data.
+pub fn decode_lookup_table<'a>(owner: &Pubkey, data: &'a [u8]) -> Option<AddressLookupTable<'a>> {
+    if *owner != address_lookup_table::program::id() {
+        return None;
+    }
+    AddressLookupTable::deserialize(data).ok()
+}
+
+/// Decodes an account of the address lookup table program into an owned lookup table.
+pub fn decode_lookup_table_account(
+    account: &impl ReadableAccount,
+) -> Option<AddressLookupTable<'static>> {
+    let lookup_table = decode_lookup_table(account.owner(), account.data())?;
+    Some(AddressLookupTable {
+        meta: lookup_table.meta,
+        addresses: lookup_table.addresses.into_owned().into(),
+    })
+}
+
+/// Iterates over the latest copy of every address lookup table, in no particular order.
+pub fn lookup_tables(
+    accounts: &LatestAccounts,
+) -> impl Iterator<Item = (Pubkey, AddressLookupTable<'_>)> {
+    accounts.iter().filter_map(|(_, handle)| {
+        let stored = handle.access()?;
+        Some((
+            stored.meta.pubkey,
+            decode_lookup_table(&stored.account_meta.owner, stored.data)?,
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{append_vec::test_utils::TestAccount, test_utils::TestExtractor},
+        solana_sdk::{
+            account::{AccountSharedData, WritableAccount},
+            address_lookup_table::state::{LookupTableMeta, LookupTableStatus},
+            slot_hashes::SlotHashes,
+        },
+        std::borrow::Cow,
+    };
+
+    fn lookup_table(deactivation_slot: u64, addresses: usize) -> AddressLookupTable<'static> {
+        AddressLookupTable {
+            meta: LookupTableMeta {
+                deactivation_slot,
+                last_extended_slot: 5,
+                last_extended_slot_start_index: 1,
+                ..LookupTableMeta::new(Pubkey::new_unique())
+            },
+            addresses: Cow::Owned((0..addresses).map(|_| Pubkey::new_unique()).collect()),
+        }
+    }
+
+    fn account(data: Vec<u8>) -> AccountSharedData {
+        AccountSharedData::create(1, data, address_lookup_table::program::id(), false, 0)
+    }
+
+    #[test]
+    fn decodes_active_and_deactivated_tables() {
+        let active = lookup_table(u64::MAX, 3);
+        let deactivated = lookup_table(10, 0);
+        let slot_hashes = SlotHashes::default();
+
+        let decoded =
+            decode_lookup_table_account(&account(active.clone().serialize_for_tests().unwrap()))
+                .unwrap();
+        assert_eq!(decoded, active);
+        assert_eq!(
+            decoded.meta.status(100, &slot_hashes),
+            LookupTableStatus::Activated
+        );
+
+        let decoded = decode_lookup_table_account(&account(
+            deactivated.clone().serialize_for_tests().unwrap(),
+        ))
+        .unwrap();
+        assert_eq!(decoded, deactivated);
+        assert_eq!(
+            decoded.meta.status(100, &slot_hashes),
+            LookupTableStatus::Deactivated
+        );
+
+        let (active_pubkey, other_pubkey) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut other = account(active.clone().serialize_for_tests().unwrap());
+        other.set_owner(Pubkey::new_unique());
+        assert!(decode_lookup_table_account(&other).is_none());
+        let files = vec![(
+            1,
+            1,
+            vec![
+                TestAccount::new(
+                    active_pubkey,
+                    account(active.clone().serialize_for_tests().unwrap()),
+                ),
+                TestAccount::new(other_pubkey, other),
+            ],
+        )];
+        let mut extractor = TestExtractor::new(files);
+        let accounts = LatestAccounts::from_extractor(&mut extractor).unwrap();
+        let tables: Vec<_> = lookup_tables(&accounts).collect();
+        assert_eq!(tables, vec![(active_pubkey, active)]);
+    }
+
+    #[test]
+    fn rejects_truncated_and_uninitialized_tables() {
+        let data = lookup_table(u64::MAX, 2).serialize_for_tests().unwrap();
+        let owner = address_lookup_table::program::id();
+        assert!(decode_lookup_table(&owner, &data).is_some());
+        // part of an address
+        assert!(decode_lookup_table(&owner, &data[..data.len() - 1]).is_none());
+        // part of the meta
+        assert!(decode_lookup_table(&owner, &data[..20]).is_none());
+        assert!(decode_lookup_table(&owner, &[]).is_none());
+        // ProgramState::Uninitialized
+        assert!(decode_lookup_table(&owner, &vec![0; data.len()]).is_none());
+    }
+}
@@ -0,0 +1,158 @@
+use {
+    crate::{
+        append_vec::StoredAccountMeta, latest::LatestAccounts, solana::SerdeAccountsLtHash,
+        SnapshotExtractor,
+    },
+    log::info,
+    solana_accounts_db::accounts_db::AccountsDb,
+    solana_lattice_hash::lt_hash::{Checksum, LtHash},
+    std::{fmt, time::Instant},
+};
+
+/// Computes the 2048-byte lattice hash contribution of an account the way the runtime does.
+/// Zero-lamport accounts contribute the identity.
+pub fn account_lt_hash(stored: &StoredAccountMeta) -> LtHash {
+    AccountsDb::lt_hash_account(stored, &stored.meta.pubkey).0
+}
+
+/// Computes the accounts lattice hash, the sum of the lattice hashes of all accounts.
+pub fn accounts_lt_hash(accounts: &LatestAccounts) -> LtHash {
+    let started = Instant::now();
+    let mut lt_hash = LtHash::identity();
+    for (_, handle) in accounts.iter() {
+        if let Some(stored) = handle.access() {
+            lt_hash.mix_in(&account_lt_hash(&stored));
+        }
+    }
+    info!(
+        "Computed accounts lt hash {} over {} accounts in {:?}",
+        lt_hash.checksum(),
+        accounts.len(),
+        started.elapsed()
+    );
+    lt_hash
+}
+
+impl From<&SerdeAccountsLtHash> for LtHash {
+    fn from(lt_hash: &SerdeAccountsLtHash) -> Self {
+        LtHash(lt_hash.0)
+    }
+}
+
+/// Result of comparing a recomputed accounts lattice hash with the one in the manifest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountsLtHashVerification {
+    /// accounts lattice hash recomputed from the account set
+    pub lt_hash: LtHash,
+    /// accounts lattice hash recorded in the manifest, if any
+    pub manifest_lt_hash: Option<LtHash>,
+}
+
+impl AccountsLtHashVerification {
+    /// Compares the accounts lattice hash of `accounts` with the manifest of `extractor`.
+    /// As the lattice hash covers the whole account state, this also holds for an incremental
+    /// snapshot combined with its full snapshot.
+    pub fn new(accounts: &LatestAccounts, extractor: &impl SnapshotExtractor) -> Self {
+        AccountsLtHashVerification {
+            lt_hash: accounts_lt_hash(accounts),
+            manifest_lt_hash: extractor
+                .extra_fields()
+                .accounts_lt_hash
+                .as_ref()
+                .map(LtHash::from),
+        }
+    }
+
+    /// Whether the recomputed lattice hash matches the manifest, if it records one.
+    pub fn matches_manifest(&self) -> Option<bool> {
+        self.manifest_lt_hash
+            .as_ref()
+            .map(|manifest_lt_hash| *manifest_lt_hash == self.lt_hash)
+    }
+
+    pub fn checksum(&self) -> Checksum {
+        self.lt_hash.checksum()
+    }
+}
+
+impl fmt::Display for AccountsLtHashVerification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "accounts lt hash checksum {}", self.checksum())?;
+        match &self.manifest_lt_hash {
+            Some(manifest_lt_hash) => write!(f, " (manifest {})", manifest_lt_hash.checksum()),
+            None => write!(f, " (not in manifest)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            append_vec::test_utils::{new_append_vec, TestAccount},
+            append_vec_iter,
+            test_utils::TestExtractor,
+        },
+        solana_sdk::{
+            account::{AccountSharedData, WritableAccount},
+            pubkey::Pubkey,
+        },
+    };
+
+    fn accounts() -> Vec<TestAccount> {
+        let mut with_data = AccountSharedData::new(7, 3, &Pubkey::new_unique());
+        with_data.set_data_from_slice(&[1, 2, 3]);
+        with_data.set_executable(true);
+        [
+            AccountSharedData::new(1, 0, &Pubkey::default()),
+            with_data,
+            AccountSharedData::new(0, 0, &Pubkey::new_unique()),
+        ]
+        .into_iter()
+        .map(|account| TestAccount::new(Pubkey::new_unique(), account))
+        .collect()
+    }
+
+    #[test]
+    fn hashes_accounts_like_the_runtime() {
+        let accounts = accounts();
+        let append_vec = new_append_vec(1, 1, &accounts);
+        let stored: Vec<_> = append_vec_iter(&append_vec)
+            .map(|handle| account_lt_hash(&handle.access().unwrap()))
+            .collect();
+        let expected: Vec<_> = accounts
+            .iter()
+            .map(|test| AccountsDb::lt_hash_account(&test.account, &test.pubkey).0)
+            .collect();
+        assert_eq!(stored, expected);
+        assert_eq!(stored[2], LtHash::identity());
+        assert_ne!(stored[0], LtHash::identity());
+    }
+
+    #[test]
+    fn compares_with_manifest() {
+        let accounts = accounts();
+        let mut expected = LtHash::identity();
+        for test in &accounts {
+            expected.mix_in(&AccountsDb::lt_hash_account(&test.account, &test.pubkey).0);
+        }
+        let mut extractor = TestExtractor::new(vec![(1, 1, accounts)]);
+        let latest = LatestAccounts::from_extractor(&mut extractor).unwrap();
+
+        let verification = AccountsLtHashVerification::new(&latest, &extractor);
+        assert_eq!(verification.lt_hash, expected);
+        assert_eq!(verification.matches_manifest(), None);
+
+        extractor.extra_fields.accounts_lt_hash = Some(SerdeAccountsLtHash(expected.0));
+        let verification = AccountsLtHashVerification::new(&latest, &extractor);
+        assert_eq!(verification.matches_manifest(), Some(true));
+
+        let mut other = expected;
+        other.0[0] = other.0[0].wrapping_add(1);
+        extractor.extra_fields.accounts_lt_hash = Some(SerdeAccountsLtHash(other.0));
+        let verification = AccountsLtHashVerification::new(&latest, &extractor);
+        assert_eq!(verification.matches_manifest(), Some(false));
+        assert_eq!(verification.manifest_lt_hash, Some(other));
+    }
+}
@@ -0,0 +1,104 @@
+use solana_sdk::{
+    account::ReadableAccount,
+    fee_calculator::FeeCalculator,
+    hash::Hash,
+    nonce::state::{State, Versions},
+    pubkey::Pubkey,
+    system_program,
+};
+
+/// Version of the nonce account layout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NonceVersion {
+    /// the durable nonce is a recent blockhash
+    Legacy,
+    /// the durable nonce is derived from a recent blockhash
+    Current,
+}
+
+/// An initialized durable transaction nonce account.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NonceAccount {
+    pub version: NonceVersion,
+    /// account that signs transactions using the nonce
+    pub authority: Pubkey,
+    /// the durable nonce, used as recent blockhash of a transaction
+    pub blockhash: Hash,
+    pub fee_calculator: FeeCalculator,
+}
+
+/// Decodes an initialized nonce account owned by the system program.
+pub fn decode_nonce_account(account: &impl ReadableAccount) -> Option<NonceAccount> {
+    if *account.owner() != system_program::id() || account.data().len() != State::size() {
+        return None;
+    }
+    let versions: Versions = bincode::deserialize(account.data()).ok()?;
+    let version = match versions {
+        Versions::Legacy(_) => NonceVersion::Legacy,
+        Versions::Current(_) => NonceVersion::Current,
+    };
+    match versions.state() {
+        State::Initialized(data) => Some(NonceAccount {
+            version,
+            authority: data.authority,
+            blockhash: data.blockhash(),
+            fee_calculator: data.fee_calculator,
+        }),
+        State::Uninitialized => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_sdk::{
+            account::{Account, WritableAccount},
+            nonce::state::{Data, DurableNonce},
+        },
+    };
+
+    fn account(versions: &Versions) -> Account {
+        let mut data = bincode::serialize(versions).unwrap();
+        data.resize(State::size(), 0);
+        Account::create(1, data, system_program::id(), false, 0)
+    }
+
+    #[test]
+    fn decodes_nonce_versions() {
+        let authority = Pubkey::new_unique();
+        let data = Data::new(
+            authority,
+            DurableNonce::from_blockhash(&Hash::new_unique()),
+            5_000,
+        );
+        let expected = |version| NonceAccount {
+            version,
+            authority,
+            blockhash: data.blockhash(),
+            fee_calculator: FeeCalculator::new(5_000),
+        };
+
+        let current = account(&Versions::new(State::Initialized(data.clone())));
+        assert_eq!(
+            decode_nonce_account(&current),
+            Some(expected(NonceVersion::Current))
+        );
+        let legacy = account(&Versions::Legacy(Box::new(State::Initialized(
+            data.clone(),
+        ))));
+        assert_eq!(
+            decode_nonce_account(&legacy),
+            Some(expected(NonceVersion::Legacy))
+        );
+
+        let uninitialized = account(&Versions::new(State::Uninitialized));
+        assert_eq!(decode_nonce_account(&uninitialized), None);
+        let mut other_owner = current.clone();
+        other_owner.owner = Pubkey::new_unique();
+        assert_eq!(decode_nonce_account(&other_owner), None);
+        let mut truncated = current;
+        truncated.data.truncate(State::size() - 1);
+        assert_eq!(decode_nonce_account(&truncated), None);
+    }
+}
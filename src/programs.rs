@@ -0,0 +1,343 @@
+use {
+    crate::latest::LatestAccounts,
+    log::info,
+    serde_json::json,
+    solana_sdk::{
+        account::ReadableAccount,
+        bpf_loader, bpf_loader_deprecated,
+        bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+        clock::Slot,
+        pubkey::Pubkey,
+    },
+    std::{fmt, fs, fs::File, io, path::Path, time::Instant},
+};
+
+/// The loader a program is deployed with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProgramLoader {
+    /// BPF loader v1, ELF stored in the program account
+    Deprecated,
+    /// BPF loader v2, ELF stored in the program account
+    Loader,
+    /// BPF loader v3, ELF stored in a separate ProgramData account
+    Upgradeable,
+}
+
+impl ProgramLoader {
+    pub fn from_owner(owner: &Pubkey) -> Option<Self> {
+        if *owner == bpf_loader_deprecated::id() {
+            Some(ProgramLoader::Deprecated)
+        } else if *owner == bpf_loader::id() {
+            Some(ProgramLoader::Loader)
+        } else if *owner == bpf_loader_upgradeable::id() {
+            Some(ProgramLoader::Upgradeable)
+        } else {
+            None
+        }
+    }
+
+    pub const fn id(self) -> Pubkey {
+        match self {
+            ProgramLoader::Deprecated => bpf_loader_deprecated::id(),
+            ProgramLoader::Loader => bpf_loader::id(),
+            ProgramLoader::Upgradeable => bpf_loader_upgradeable::id(),
+        }
+    }
+}
+
+impl fmt::Display for ProgramLoader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProgramLoader::Deprecated => write!(f, "bpf_loader_deprecated"),
+            ProgramLoader::Loader => write!(f, "bpf_loader"),
+            ProgramLoader::Upgradeable => write!(f, "bpf_loader_upgradeable"),
+        }
+    }
+}
+
+/// A deployed program with its ELF, borrowed from the accounts files.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeployedProgram<'a> {
+    pub program_id: Pubkey,
+    pub loader: ProgramLoader,
+    /// ProgramData account of an upgradeable program
+    pub programdata_address: Option<Pubkey>,
+    /// None for immutable programs and programs of the non-upgradeable loaders
+    pub upgrade_authority: Option<Pubkey>,
+    /// slot the program was last deployed at, only known for upgradeable programs
+    pub last_deploy_slot: Option<Slot>,
+    /// the ELF, including any unused space at the end of the ProgramData account
+    pub elf: &'a [u8],
+}
+
+/// Finds all executable programs of the BPF loaders. Upgradeable programs are resolved to
+/// their ProgramData account, closed upgradeable programs are skipped.
+/// Programs are sorted by program id.
+pub fn deployed_programs(accounts: &LatestAccounts) -> Vec<DeployedProgram<'_>> {
+    let started = Instant::now();
+    let mut programs: Vec<DeployedProgram> = accounts
+        .iter()
+        .filter_map(|(_, handle)| {
+            let stored = handle.access()?;
+            let loader = ProgramLoader::from_owner(stored.owner())?;
+            if loader != ProgramLoader::Upgradeable {
+                return stored.executable().then_some(DeployedProgram {
+                    program_id: stored.meta.pubkey,
+                    loader,
+                    programdata_address: None,
+                    upgrade_authority: None,
+                    last_deploy_slot: None,
+                    elf: stored.data,
+                });
+            }
+            let UpgradeableLoaderState::Program {
+                programdata_address,
+            } = bincode::deserialize(stored.data).ok()?
+            else {
+                return None;
+            };
+            let programdata = accounts.get(&programdata_address)?;
+            let UpgradeableLoaderState::ProgramData {
+                slot,
+                upgrade_authority_address,
+            } = bincode::deserialize(programdata.data).ok()?
+            else {
+                return None;
+            };
+            Some(DeployedProgram {
+                program_id: stored.meta.pubkey,
+                loader,
+                programdata_address: Some(programdata_address),
+                upgrade_authority: upgrade_authority_address,
+                last_deploy_slot: Some(slot),
+                elf: programdata
+                    .data
+                    .get(UpgradeableLoaderState::size_of_programdata_metadata()..)?,
+            })
+        })
+        .collect();
+    programs.sort_unstable_by_key(|program| program.program_id);
+    info!(
+        "Found {} deployed programs in {:?}",
+        programs.len(),
+        started.elapsed()
+    );
+    programs
+}
+
+/// Writes the ELF of each program to `<program_id>.so` in `output_dir`, along with a
+/// `manifest.json` listing the loader, ProgramData account, upgrade authority, last deploy slot
+/// and ELF size of each program.
+pub fn write_programs(programs: &[DeployedProgram], output_dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(output_dir)?;
+    let mut manifest = Vec::with_capacity(programs.len());
+    for program in programs {
+        fs::write(
+            output_dir.join(format!("{}.so", program.program_id)),
+            program.elf,
+        )?;
+        manifest.push(json!({
+            "program_id": program.program_id.to_string(),
+            "loader": program.loader.to_string(),
+            "programdata_address": program.programdata_address.map(|address| address.to_string()),
+            "upgrade_authority": program.upgrade_authority.map(|authority| authority.to_string()),
+            "last_deploy_slot": program.last_deploy_slot,
+            "size": program.elf.len(),
+        }));
+    }
+    serde_json::to_writer_pretty(File::create(output_dir.join("manifest.json"))?, &manifest)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{append_vec::test_utils::TestAccount, test_utils::TestExtractor},
+        solana_sdk::account::{AccountSharedData, WritableAccount},
+    };
+
+    fn account(
+        pubkey: Pubkey,
+        lamports: u64,
+        data: Vec<u8>,
+        owner: Pubkey,
+        executable: bool,
+    ) -> TestAccount {
+        TestAccount::new(
+            pubkey,
+            AccountSharedData::create(lamports, data, owner, executable, 0),
+        )
+    }
+
+    fn upgradeable(pubkey: Pubkey, state: &UpgradeableLoaderState, elf: &[u8]) -> TestAccount {
+        let mut data = bincode::serialize(state).unwrap();
+        if let UpgradeableLoaderState::ProgramData { .. } = state {
+            data.resize(UpgradeableLoaderState::size_of_programdata_metadata(), 0);
+        }
+        data.extend_from_slice(elf);
+        account(
+            pubkey,
+            1,
+            data,
+            bpf_loader_upgradeable::id(),
+            !elf.is_empty(),
+        )
+    }
+
+    fn program(programdata_address: Pubkey) -> UpgradeableLoaderState {
+        UpgradeableLoaderState::Program {
+            programdata_address,
+        }
+    }
+
+    #[test]
+    fn resolves_deployed_programs() {
+        let mut ids: Vec<_> = (0..6).map(|_| Pubkey::new_unique()).collect();
+        ids.sort();
+        let [upgradeable_id, closed_id, missing_id, buffer_id, loader_id, data_id] = ids[..] else {
+            unreachable!()
+        };
+        let (programdata, closed_programdata, buffer) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let authority = Pubkey::new_unique();
+        let elf = b"\x7fELF upgradeable";
+        let files = vec![
+            (
+                5,
+                1,
+                vec![
+                    upgradeable(upgradeable_id, &program(programdata), &[]),
+                    upgradeable(
+                        programdata,
+                        &UpgradeableLoaderState::ProgramData {
+                            slot: 4,
+                            upgrade_authority_address: Some(authority),
+                        },
+                        elf,
+                    ),
+                    upgradeable(closed_id, &program(closed_programdata), &[]),
+                    upgradeable(
+                        closed_programdata,
+                        &UpgradeableLoaderState::ProgramData {
+                            slot: 2,
+                            upgrade_authority_address: None,
+                        },
+                        elf,
+                    ),
+                    // ProgramData that was never stored
+                    upgradeable(missing_id, &program(Pubkey::new_unique()), &[]),
+                    // pointing to a buffer instead of ProgramData
+                    upgradeable(buffer_id, &program(buffer), &[]),
+                    upgradeable(
+                        buffer,
+                        &UpgradeableLoaderState::Buffer {
+                            authority_address: None,
+                        },
+                        elf,
+                    ),
+                    account(loader_id, 1, b"\x7fELF v2".to_vec(), bpf_loader::id(), true),
+                    // not executable
+                    account(data_id, 1, b"\x7fELF".to_vec(), bpf_loader::id(), false),
+                ],
+            ),
+            // closing the program empties its ProgramData account
+            (
+                6,
+                1,
+                vec![account(
+                    closed_programdata,
+                    0,
+                    Vec::new(),
+                    Pubkey::default(),
+                    false,
+                )],
+            ),
+        ];
+        let mut extractor = TestExtractor::new(files);
+        let accounts = LatestAccounts::from_extractor(&mut extractor).unwrap();
+
+        assert_eq!(
+            deployed_programs(&accounts),
+            vec![
+                DeployedProgram {
+                    program_id: upgradeable_id,
+                    loader: ProgramLoader::Upgradeable,
+                    programdata_address: Some(programdata),
+                    upgrade_authority: Some(authority),
+                    last_deploy_slot: Some(4),
+                    elf,
+                },
+                DeployedProgram {
+                    program_id: loader_id,
+                    loader: ProgramLoader::Loader,
+                    programdata_address: None,
+                    upgrade_authority: None,
+                    last_deploy_slot: None,
+                    elf: b"\x7fELF v2",
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_programs_with_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let (upgradeable_id, programdata, loader_id) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let programs = [
+            DeployedProgram {
+                program_id: upgradeable_id,
+                loader: ProgramLoader::Upgradeable,
+                programdata_address: Some(programdata),
+                upgrade_authority: None,
+                last_deploy_slot: Some(4),
+                elf: b"upgradeable",
+            },
+            DeployedProgram {
+                program_id: loader_id,
+                loader: ProgramLoader::Deprecated,
+                programdata_address: None,
+                upgrade_authority: None,
+                last_deploy_slot: None,
+                elf: b"deprecated",
+            },
+        ];
+        let output_dir = dir.path().join("programs");
+        write_programs(&programs, &output_dir).unwrap();
+
+        for program in &programs {
+            let path = output_dir.join(format!("{}.so", program.program_id));
+            assert_eq!(fs::read(path).unwrap(), program.elf);
+        }
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&fs::read(output_dir.join("manifest.json")).unwrap()).unwrap();
+        assert_eq!(
+            manifest,
+            json!([
+                {
+                    "program_id": upgradeable_id.to_string(),
+                    "loader": "bpf_loader_upgradeable",
+                    "programdata_address": programdata.to_string(),
+                    "upgrade_authority": null,
+                    "last_deploy_slot": 4,
+                    "size": 11,
+                },
+                {
+                    "program_id": loader_id.to_string(),
+                    "loader": "bpf_loader_deprecated",
+                    "programdata_address": null,
+                    "upgrade_authority": null,
+                    "last_deploy_slot": null,
+                    "size": 10,
+                },
+            ])
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use {
+    crate::{
+        accounts_hash::{merkle_parent_level, sorted_account_hashes, MERKLE_FANOUT},
+        latest::LatestAccounts,
+    },
+    serde::{Deserialize, Serialize},
+    solana_accounts_db::accounts_db::AccountsDb,
+    solana_sdk::{
+        account::ReadableAccount,
+        hash::{Hash, Hasher},
+        pubkey::Pubkey,
+    },
+};
+
+/// One level of an inclusion proof: the position of the node within its chunk of up to
+/// `MERKLE_FANOUT` nodes, and the other nodes of that chunk in order.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofLevel {
+    pub index: usize,
+    #[serde(with = "base58::hashes")]
+    pub siblings: Vec<Hash>,
+}
+
+/// Proves that an account with a given hash is part of the accounts hash Merkle tree.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountProof {
+    #[serde(with = "base58::pubkey")]
+    pub pubkey: Pubkey,
+    #[serde(with = "base58::hash")]
+    pub account_hash: Hash,
+    /// levels from the leaves up to the root
+    pub levels: Vec<ProofLevel>,
+}
+
+impl AccountProof {
+    /// Computes the root the proof leads to.
+    pub fn root(&self) -> Option<Hash> {
+        self.levels
+            .iter()
+            .try_fold(self.account_hash, |node, level| {
+                if level.index > level.siblings.len() || level.siblings.len() >= MERKLE_FANOUT {
+                    return None;
+                }
+                let mut hasher = Hasher::default();
+                let (before, after) = level.siblings.split_at(level.index);
+                for hash in before.iter().chain(std::iter::once(&node)).chain(after) {
+                    hasher.hash(hash.as_ref());
+                }
+                Some(hasher.result())
+            })
+    }
+
+    /// Checks the proof against an accounts hash.
+    pub fn verify(&self, accounts_hash: &Hash) -> bool {
+        !self.levels.is_empty() && self.root().as_ref() == Some(accounts_hash)
+    }
+
+    /// Checks that the proof is for `account` and leads to `accounts_hash`.
+    pub fn verify_account(&self, account: &impl ReadableAccount, accounts_hash: &Hash) -> bool {
+        AccountsDb::hash_account(account, &self.pubkey).0 == self.account_hash
+            && self.verify(accounts_hash)
+    }
+}
+
+/// The accounts hash Merkle tree of a snapshot, kept in memory to produce inclusion proofs.
+pub struct AccountsMerkleTree {
+    pubkeys: Vec<Pubkey>,
+    /// levels from the account hashes up to the root
+    levels: Vec<Vec<Hash>>,
+}
+
+impl AccountsMerkleTree {
+    pub fn new(accounts: &LatestAccounts) -> Self {
+        Self::from_sorted_hashes(sorted_account_hashes(accounts))
+    }
+
+    /// Builds the tree over account hashes sorted by pubkey.
+    pub fn from_sorted_hashes(hashes: Vec<(Pubkey, Hash)>) -> Self {
+        let (pubkeys, leaves): (Vec<Pubkey>, Vec<Hash>) = hashes.into_iter().unzip();
+        let mut levels = vec![leaves];
+        if !levels[0].is_empty() {
+            loop {
+                let parent = merkle_parent_level(levels.last().expect("not empty"));
+                let is_root = parent.len() == 1;
+                levels.push(parent);
+                if is_root {
+                    break;
+                }
+            }
+        }
+        AccountsMerkleTree { pubkeys, levels }
+    }
+
+    /// The accounts hash.
+    pub fn root(&self) -> Hash {
+        match self.levels.len() {
+            1 => Hasher::default().result(),
+            _ => self.levels.last().expect("checked")[0],
+        }
+    }
+
+    pub fn prove(&self, pubkey: &Pubkey) -> Option<AccountProof> {
+        let mut index = self.pubkeys.binary_search(pubkey).ok()?;
+        let account_hash = self.levels[0][index];
+        let levels = self.levels[..self.levels.len() - 1]
+            .iter()
+            .map(|level| {
+                let chunk_start = index - index % MERKLE_FANOUT;
+                let chunk_end = (chunk_start + MERKLE_FANOUT).min(level.len());
+                let proof_level = ProofLevel {
+                    index: index - chunk_start,
+                    siblings: level[chunk_start..chunk_end]
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| chunk_start + i != index)
+                        .map(|(_, hash)| *hash)
+                        .collect(),
+                };
+                index /= MERKLE_FANOUT;
+                proof_level
+            })
+            .collect();
+        Some(AccountProof {
+            pubkey: *pubkey,
+            account_hash,
+            levels,
+        })
+    }
+}
+
+/// Serializes hashes and pubkeys as base58 strings.
+mod base58 {
+    use {
+        serde::{de::Error, Deserialize, Deserializer, Serializer},
+        std::{fmt::Display, str::FromStr},
+    };
+
+    fn parse<'de, T, D>(value: &str) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        value.parse().map_err(D::Error::custom)
+    }
+
+    pub mod hash {
+        use {super::*, solana_sdk::hash::Hash};
+
+        pub fn serialize<S: Serializer>(hash: &Hash, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_str(hash)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Hash, D::Error> {
+            parse::<_, D>(&String::deserialize(deserializer)?)
+        }
+    }
+
+    pub mod hashes {
+        use {super::*, serde::ser::SerializeSeq, solana_sdk::hash::Hash};
+
+        pub fn serialize<S: Serializer>(hashes: &[Hash], serializer: S) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(Some(hashes.len()))?;
+            for hash in hashes {
+                seq.serialize_element(&hash.to_string())?;
+            }
+            seq.end()
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Vec<Hash>, D::Error> {
+            Vec::<String>::deserialize(deserializer)?
+                .iter()
+                .map(|hash| parse::<_, D>(hash))
+                .collect()
+        }
+    }
+
+    pub mod pubkey {
+        use {super::*, solana_sdk::pubkey::Pubkey};
+
+        pub fn serialize<S: Serializer>(pubkey: &Pubkey, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_str(pubkey)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pubkey, D::Error> {
+            parse::<_, D>(&String::deserialize(deserializer)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::accounts_hash::compute_merkle_root,
+        solana_sdk::{account::AccountSharedData, hash::hashv},
+    };
+
+    /// A tree over `count` accounts with sorted pubkeys and one lamport more each.
+    fn tree(count: u64) -> (AccountsMerkleTree, Vec<(Pubkey, AccountSharedData)>) {
+        let owner = Pubkey::new_unique();
+        let mut accounts: Vec<(Pubkey, AccountSharedData)> = (0..count)
+            .map(|i| {
+                let account = AccountSharedData::new(i + 1, 0, &owner);
+                (Pubkey::new_unique(), account)
+            })
+            .collect();
+        accounts.sort_unstable_by_key(|(pubkey, _)| *pubkey);
+        let hashes = accounts
+            .iter()
+            .map(|(pubkey, account)| (*pubkey, AccountsDb::hash_account(account, pubkey).0))
+            .collect();
+        (AccountsMerkleTree::from_sorted_hashes(hashes), accounts)
+    }
+
+    #[test]
+    fn root_matches_accounts_hash() {
+        for count in [1, 16, 17, 300] {
+            let (tree, _) = tree(count);
+            let leaves = tree.levels[0].clone();
+            assert_eq!(tree.root(), compute_merkle_root(leaves), "{} leaves", count);
+        }
+    }
+
+    #[test]
+    fn proves_every_account() {
+        for count in [1, 16, 17, 257, 300] {
+            let (tree, accounts) = tree(count);
+            for (pubkey, account) in &accounts {
+                let proof = tree.prove(pubkey).unwrap();
+                assert!(proof.verify_account(account, &tree.root()), "{}", pubkey);
+            }
+        }
+    }
+
+    #[test]
+    fn proof_levels_of_17_leaves() {
+        let (tree, accounts) = tree(17);
+        let leaves = &tree.levels[0];
+
+        // the last leaf is alone in the second chunk
+        let proof = tree.prove(&accounts[16].0).unwrap();
+        assert_eq!(proof.account_hash, leaves[16]);
+        let first_chunk = hashv(&leaves[..16].iter().map(Hash::as_ref).collect::<Vec<_>>());
+        assert_eq!(
+            proof.levels,
+            vec![
+                ProofLevel {
+                    index: 0,
+                    siblings: vec![],
+                },
+                ProofLevel {
+                    index: 1,
+                    siblings: vec![first_chunk],
+                },
+            ]
+        );
+
+        // a leaf within the first chunk
+        let proof = tree.prove(&accounts[5].0).unwrap();
+        assert_eq!(proof.levels[0].index, 5);
+        assert_eq!(proof.levels[0].siblings.len(), 15);
+        assert_eq!(proof.levels[1].index, 0);
+        assert_eq!(
+            proof.levels[1].siblings,
+            vec![hashv(&[leaves[16].as_ref()])]
+        );
+    }
+
+    #[test]
+    fn rejects_tampered_proofs() {
+        let (tree, accounts) = tree(17);
+        let (pubkey, account) = &accounts[3];
+        let proof = tree.prove(pubkey).unwrap();
+        assert!(proof.verify_account(account, &tree.root()));
+
+        let mut tampered = proof.clone();
+        tampered.levels[0].siblings[0] = Hash::new_unique();
+        assert!(!tampered.verify(&tree.root()));
+
+        let mut tampered = proof.clone();
+        tampered.levels[0].index = 4;
+        assert!(!tampered.verify(&tree.root()));
+
+        let mut tampered = proof.clone();
+        tampered.levels[0].index = MERKLE_FANOUT;
+        assert_eq!(tampered.root(), None);
+
+        let mut tampered = proof.clone();
+        tampered.levels.clear();
+        assert!(!tampered.verify(&tampered.account_hash));
+
+        let other = AccountSharedData::new(1000, 0, &Pubkey::new_unique());
+        assert!(!proof.verify_account(&other, &tree.root()));
+        assert!(!proof.verify(&Hash::new_unique()));
+    }
+
+    #[test]
+    fn no_proof_of_missing_account() {
+        let (tree, _) = tree(17);
+        assert_eq!(tree.prove(&Pubkey::new_unique()), None);
+    }
+
+    #[test]
+    fn proof_json_round_trip() {
+        let (tree, accounts) = tree(17);
+        let proof = tree.prove(&accounts[16].0).unwrap();
+        let json = serde_json::to_string(&proof).unwrap();
+        assert!(json.contains(&accounts[16].0.to_string()));
+        assert_eq!(serde_json::from_str::<AccountProof>(&json).unwrap(), proof);
+    }
+}
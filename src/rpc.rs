@@ -0,0 +1,1106 @@
+use {
+    crate::{
+        filter::AccountFilters, indexed::IndexedAccounts, token::decode_token, SnapshotResult,
+    },
+    base64::{prelude::BASE64_STANDARD, Engine},
+    bytemuck::Pod,
+    hyper::{
+        header,
+        service::{make_service_fn, service_fn},
+        Body, Method, Request, Response, Server, StatusCode,
+    },
+    log::{info, warn},
+    serde::Deserialize,
+    serde_json::{json, Value},
+    solana_account_decoder::{
+        encode_ui_account,
+        parse_account_data::{AccountAdditionalDataV2, SplTokenAdditionalData},
+        parse_token::{get_token_account_mint, is_known_spl_token_id},
+        UiAccountEncoding, UiDataSliceConfig, MAX_BASE58_BYTES,
+    },
+    solana_accounts_db::accounts_index::AccountIndex,
+    solana_sdk::{
+        account::{AccountSharedData, ReadableAccount},
+        clock::{Clock, Slot, UnixTimestamp},
+        program_pack::Pack,
+        pubkey::Pubkey,
+        sysvar,
+    },
+    spl_token_2022::{
+        extension::{
+            interest_bearing_mint::InterestBearingConfig, BaseStateWithExtensions,
+            StateWithExtensions,
+        },
+        state::{Account, Mint},
+    },
+    std::{convert::Infallible, net::SocketAddr, str::FromStr, sync::Arc},
+    thiserror::Error,
+};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+pub const KEY_EXCLUDED_FROM_SECONDARY_INDEX: i64 = -32010;
+pub const MIN_CONTEXT_SLOT_NOT_REACHED: i64 = -32016;
+
+/// Limits of the validator RPC.
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+const MAX_FILTERS: usize = 4;
+
+/// A JSON-RPC error with the code and message the validator RPC answers with.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+#[error("{message} ({code})")]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(INVALID_PARAMS, message)
+    }
+
+    fn internal(error: impl ToString) -> Self {
+        Self::new(INTERNAL_ERROR, error.to_string())
+    }
+
+    fn secondary_index_not_open(method: &str, kind: &AccountIndex) -> Self {
+        Self::new(
+            KEY_EXCLUDED_FROM_SECONDARY_INDEX,
+            format!(
+                "{} is unavailable without the {} account index",
+                method,
+                account_index_name(kind)
+            ),
+        )
+    }
+}
+
+pub type RpcResult<T> = Result<T, RpcError>;
+
+/// Config of the methods returning accounts. The commitment is accepted but ignored, as a
+/// snapshot has a single slot.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccountInfoConfig {
+    encoding: Option<UiAccountEncoding>,
+    data_slice: Option<UiDataSliceConfig>,
+    min_context_slot: Option<Slot>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ContextConfig {
+    min_context_slot: Option<Slot>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProgramAccountsConfig {
+    #[serde(flatten)]
+    account_config: AccountInfoConfig,
+    filters: Option<Vec<RpcFilter>>,
+    with_context: Option<bool>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum RpcFilter {
+    DataSize(u64),
+    Memcmp(RpcMemcmp),
+    /// only valid token accounts
+    TokenAccountState,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct RpcMemcmp {
+    offset: usize,
+    /// a string in `encoding`, or an array of bytes
+    bytes: Value,
+    encoding: Option<String>,
+}
+
+impl RpcMemcmp {
+    fn bytes(&self) -> RpcResult<Vec<u8>> {
+        let invalid = || RpcError::invalid_params("Invalid param: could not decode memcmp bytes");
+        match (&self.bytes, self.encoding.as_deref()) {
+            (Value::String(bytes), None | Some("base58" | "binary")) => {
+                bs58::decode(bytes).into_vec().map_err(|_| invalid())
+            }
+            (Value::String(bytes), Some("base64")) => {
+                BASE64_STANDARD.decode(bytes).map_err(|_| invalid())
+            }
+            (Value::Array(_), None | Some("bytes")) => {
+                serde_json::from_value(self.bytes.clone()).map_err(|_| invalid())
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum TokenAccountsFilter {
+    Mint(String),
+    ProgramId(String),
+}
+
+/// Answers the account queries of the Solana JSON-RPC API from the accounts of a snapshot:
+/// `getAccountInfo`, `getMultipleAccounts`, `getProgramAccounts`, `getBalance`,
+/// `getTokenAccountsByOwner` and `getSlot`.
+///
+/// Responses have the context slot of the snapshot and encode accounts like the validator,
+/// including `jsonParsed` token accounts. Like a validator started without `--account-index`,
+/// queries by program or token owner are refused unless the `ProgramId` or `SplTokenOwner`
+/// secondary index is open, instead of scanning the whole snapshot for each request.
+/// `getProgramAccounts` for token accounts of one owner or mint can also use the
+/// `SplTokenOwner` or `SplTokenMint` index.
+pub struct RpcHandler {
+    accounts: IndexedAccounts,
+    slot: Slot,
+    /// time of the Clock sysvar, to accrue the interest of interest-bearing mints
+    unix_timestamp: UnixTimestamp,
+}
+
+impl RpcHandler {
+    pub fn new(accounts: IndexedAccounts) -> SnapshotResult<Self> {
+        let unix_timestamp = accounts
+            .get_account(&sysvar::clock::id())?
+            .and_then(|account| bincode::deserialize::<Clock>(account.data()).ok())
+            .map_or(0, |clock| clock.unix_timestamp);
+        for (method, kind) in [
+            ("getProgramAccounts", AccountIndex::ProgramId),
+            ("getTokenAccountsByOwner", AccountIndex::SplTokenOwner),
+        ] {
+            if accounts.secondary_index(&kind).is_none() {
+                warn!(
+                    "{} is unavailable without the {} account index",
+                    method,
+                    account_index_name(&kind)
+                );
+            }
+        }
+        Ok(Self {
+            slot: accounts.index().slot(),
+            accounts,
+            unix_timestamp,
+        })
+    }
+
+    pub const fn accounts(&self) -> &IndexedAccounts {
+        &self.accounts
+    }
+
+    /// Answers the body of a HTTP request holding a single or a batch of JSON-RPC calls.
+    pub fn handle_request(&self, body: &[u8]) -> Value {
+        match serde_json::from_slice::<Value>(body) {
+            Ok(Value::Array(calls)) if !calls.is_empty() => calls
+                .into_iter()
+                .map(|call| self.handle_call(call))
+                .collect(),
+            Ok(Value::Array(_)) => error_response(
+                Value::Null,
+                RpcError::new(INVALID_REQUEST, "Invalid request"),
+            ),
+            Ok(call) => self.handle_call(call),
+            Err(_) => error_response(Value::Null, RpcError::new(PARSE_ERROR, "Parse error")),
+        }
+    }
+
+    fn handle_call(&self, call: Value) -> Value {
+        let id = call.get("id").cloned().unwrap_or(Value::Null);
+        let Some(method) = call.get("method").and_then(Value::as_str) else {
+            return error_response(id, RpcError::new(INVALID_REQUEST, "Invalid request"));
+        };
+        let params = match call.get("params") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(params)) => params.clone(),
+            Some(_) => {
+                return error_response(
+                    id,
+                    RpcError::invalid_params("Invalid params: expected an array"),
+                )
+            }
+        };
+        match self.call(method, &params) {
+            Ok(result) => json!({"jsonrpc": "2.0", "result": result, "id": id}),
+            Err(err) => error_response(id, err),
+        }
+    }
+
+    /// Calls a method with its positional parameters.
+    pub fn call(&self, method: &str, params: &[Value]) -> RpcResult<Value> {
+        match method {
+            "getAccountInfo" => self.get_account_info(params),
+            "getBalance" => self.get_balance(params),
+            "getMultipleAccounts" => self.get_multiple_accounts(params),
+            "getProgramAccounts" => self.get_program_accounts(params),
+            "getSlot" => self.get_slot(params),
+            "getTokenAccountsByOwner" => self.get_token_accounts_by_owner(params),
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, "Method not found")),
+        }
+    }
+
+    fn get_slot(&self, params: &[Value]) -> RpcResult<Value> {
+        let config: ContextConfig = parse_config(params.first())?;
+        self.check_min_context_slot(config.min_context_slot)?;
+        Ok(json!(self.slot))
+    }
+
+    fn get_balance(&self, params: &[Value]) -> RpcResult<Value> {
+        let pubkey = parse_pubkey(params.first())?;
+        let config: ContextConfig = parse_config(params.get(1))?;
+        self.check_min_context_slot(config.min_context_slot)?;
+        let lamports = self
+            .accounts
+            .get_account(&pubkey)
+            .map_err(RpcError::internal)?
+            .map_or(0, |account| account.lamports());
+        Ok(self.with_context(json!(lamports)))
+    }
+
+    fn get_account_info(&self, params: &[Value]) -> RpcResult<Value> {
+        let pubkey = parse_pubkey(params.first())?;
+        let config: AccountInfoConfig = parse_config(params.get(1))?;
+        self.check_min_context_slot(config.min_context_slot)?;
+        let encoding = config.encoding.unwrap_or(UiAccountEncoding::Binary);
+        let value = match self
+            .accounts
+            .get_account(&pubkey)
+            .map_err(RpcError::internal)?
+        {
+            Some(account) => self.encode_account(&pubkey, &account, encoding, config.data_slice)?,
+            None => Value::Null,
+        };
+        Ok(self.with_context(value))
+    }
+
+    fn get_multiple_accounts(&self, params: &[Value]) -> RpcResult<Value> {
+        let pubkeys = params.first().and_then(Value::as_array).ok_or_else(|| {
+            RpcError::invalid_params("Invalid params: expected an array of pubkeys")
+        })?;
+        if pubkeys.len() > MAX_MULTIPLE_ACCOUNTS {
+            return Err(RpcError::invalid_params(format!(
+                "Too many inputs provided; max {}",
+                MAX_MULTIPLE_ACCOUNTS
+            )));
+        }
+        let pubkeys = pubkeys
+            .iter()
+            .map(|pubkey| parse_pubkey(Some(pubkey)))
+            .collect::<RpcResult<Vec<_>>>()?;
+        let config: AccountInfoConfig = parse_config(params.get(1))?;
+        self.check_min_context_slot(config.min_context_slot)?;
+        let encoding = config.encoding.unwrap_or(UiAccountEncoding::Base64);
+        let accounts = self
+            .accounts
+            .get_multiple_accounts(&pubkeys)
+            .map_err(RpcError::internal)?;
+        let value = pubkeys
+            .iter()
+            .zip(accounts)
+            .map(|(pubkey, account)| match account {
+                Some(account) => self.encode_account(pubkey, &account, encoding, config.data_slice),
+                None => Ok(Value::Null),
+            })
+            .collect::<RpcResult<Vec<_>>>()?;
+        Ok(self.with_context(json!(value)))
+    }
+
+    fn get_program_accounts(&self, params: &[Value]) -> RpcResult<Value> {
+        let program_id = parse_pubkey(params.first())?;
+        let config: ProgramAccountsConfig = parse_config(params.get(1))?;
+        self.check_min_context_slot(config.account_config.min_context_slot)?;
+        let rpc_filters = config.filters.unwrap_or_default();
+        if rpc_filters.len() > MAX_FILTERS {
+            return Err(RpcError::invalid_params(format!(
+                "Too many filters provided; max {}",
+                MAX_FILTERS
+            )));
+        }
+        let mut filters = AccountFilters::new();
+        let mut token_account_state = false;
+        for filter in rpc_filters {
+            match filter {
+                RpcFilter::DataSize(data_size) => filters = filters.with_data_size(data_size),
+                RpcFilter::Memcmp(memcmp) => {
+                    filters = filters.with_memcmp(memcmp.offset, memcmp.bytes()?)
+                }
+                RpcFilter::TokenAccountState => token_account_state = true,
+            }
+        }
+
+        let accounts = self
+            .program_accounts(&program_id, &filters, token_account_state)?
+            .ok_or_else(|| {
+                RpcError::secondary_index_not_open("getProgramAccounts", &AccountIndex::ProgramId)
+            })?;
+        let encoding = config
+            .account_config
+            .encoding
+            .unwrap_or(UiAccountEncoding::Binary);
+        let value =
+            json!(self.keyed_accounts(accounts, encoding, config.account_config.data_slice)?);
+        Ok(if config.with_context.unwrap_or_default() {
+            self.with_context(value)
+        } else {
+            value
+        })
+    }
+
+    /// Like the validator, uses the token owner or mint index for token accounts of one owner or
+    /// mint, recognized by the size of token accounts and the owner or mint at its offset.
+    /// Returns None if neither that nor the program id index is open.
+    fn program_accounts(
+        &self,
+        program_id: &Pubkey,
+        filters: &AccountFilters,
+        token_account_state: bool,
+    ) -> RpcResult<Option<Vec<(Pubkey, AccountSharedData)>>> {
+        let token_accounts = is_known_spl_token_id(program_id)
+            && (token_account_state || filters.data_size() == Some(Account::LEN as u64));
+        if token_accounts {
+            for memcmp in filters.memcmp() {
+                let kind = match memcmp.offset {
+                    0 => AccountIndex::SplTokenMint,
+                    32 => AccountIndex::SplTokenOwner,
+                    _ => continue,
+                };
+                let Ok(key) = Pubkey::try_from(memcmp.bytes.as_slice()) else {
+                    continue;
+                };
+                if self.accounts.secondary_index(&kind).is_none() {
+                    continue;
+                }
+                let accounts = match kind {
+                    AccountIndex::SplTokenMint => self.accounts.get_token_accounts_by_mint(&key),
+                    _ => self.accounts.get_token_accounts_by_owner(&key),
+                }
+                .map_err(RpcError::internal)?;
+                return Ok(Some(
+                    accounts
+                        .into_iter()
+                        .filter(|(pubkey, account)| {
+                            account.owner() == program_id
+                                && filters.matches_account(pubkey, account)
+                        })
+                        .filter(|(_, account)| {
+                            !token_account_state || decode_token(account).is_some()
+                        })
+                        .collect(),
+                ));
+            }
+        }
+        if self
+            .accounts
+            .secondary_index(&AccountIndex::ProgramId)
+            .is_none()
+        {
+            return Ok(None);
+        }
+        let mut accounts = self
+            .accounts
+            .get_program_accounts(program_id, filters)
+            .map_err(RpcError::internal)?;
+        if token_account_state {
+            accounts.retain(|(_, account)| decode_token(account).is_some());
+        }
+        Ok(Some(accounts))
+    }
+
+    fn get_token_accounts_by_owner(&self, params: &[Value]) -> RpcResult<Value> {
+        let owner = parse_pubkey(params.first())?;
+        let filter: TokenAccountsFilter = params
+            .get(1)
+            .ok_or_else(|| RpcError::invalid_params("Invalid params: missing mint or programId"))
+            .and_then(|filter| {
+                serde_json::from_value(filter.clone())
+                    .map_err(|err| RpcError::invalid_params(format!("Invalid params: {}", err)))
+            })?;
+        let config: AccountInfoConfig = parse_config(params.get(2))?;
+        self.check_min_context_slot(config.min_context_slot)?;
+        if self
+            .accounts
+            .secondary_index(&AccountIndex::SplTokenOwner)
+            .is_none()
+        {
+            return Err(RpcError::secondary_index_not_open(
+                "getTokenAccountsByOwner",
+                &AccountIndex::SplTokenOwner,
+            ));
+        }
+
+        let (program_id, mint) = match filter {
+            TokenAccountsFilter::Mint(mint) => {
+                let mint = parse_pubkey(Some(&Value::String(mint)))?;
+                let program_id = self
+                    .accounts
+                    .get_account(&mint)
+                    .map_err(RpcError::internal)?
+                    .map(|account| *account.owner())
+                    .filter(is_known_spl_token_id)
+                    .ok_or_else(|| {
+                        RpcError::invalid_params("Invalid param: could not find mint")
+                    })?;
+                (program_id, Some(mint))
+            }
+            TokenAccountsFilter::ProgramId(program_id) => {
+                let program_id = parse_pubkey(Some(&Value::String(program_id)))?;
+                if !is_known_spl_token_id(&program_id) {
+                    return Err(RpcError::invalid_params(
+                        "Invalid param: unrecognized Token program id",
+                    ));
+                }
+                (program_id, None)
+            }
+        };
+        let accounts = self
+            .accounts
+            .get_token_accounts_by_owner(&owner)
+            .map_err(RpcError::internal)?
+            .into_iter()
+            .filter(|(_, account)| {
+                *account.owner() == program_id
+                    && mint.is_none_or(|mint| get_token_account_mint(account.data()) == Some(mint))
+            })
+            .collect();
+        let encoding = config.encoding.unwrap_or(UiAccountEncoding::Binary);
+        let value = json!(self.keyed_accounts(accounts, encoding, config.data_slice)?);
+        Ok(self.with_context(value))
+    }
+
+    fn keyed_accounts(
+        &self,
+        accounts: Vec<(Pubkey, AccountSharedData)>,
+        encoding: UiAccountEncoding,
+        data_slice: Option<UiDataSliceConfig>,
+    ) -> RpcResult<Vec<Value>> {
+        accounts
+            .into_iter()
+            .map(|(pubkey, account)| {
+                Ok(json!({
+                    "pubkey": pubkey.to_string(),
+                    "account": self.encode_account(&pubkey, &account, encoding, data_slice)?,
+                }))
+            })
+            .collect()
+    }
+
+    fn encode_account(
+        &self,
+        pubkey: &Pubkey,
+        account: &AccountSharedData,
+        encoding: UiAccountEncoding,
+        data_slice: Option<UiDataSliceConfig>,
+    ) -> RpcResult<Value> {
+        if matches!(
+            encoding,
+            UiAccountEncoding::Binary | UiAccountEncoding::Base58
+        ) {
+            let data_len = account.data().len();
+            let encoded_len = data_slice.map_or(data_len, |slice| {
+                slice.length.min(data_len.saturating_sub(slice.offset))
+            });
+            if encoded_len > MAX_BASE58_BYTES {
+                return Err(RpcError::new(
+                    INVALID_REQUEST,
+                    format!(
+                        "Encoded binary (base 58) data should be less than {} bytes, please use Base64 encoding.",
+                        MAX_BASE58_BYTES
+                    ),
+                ));
+            }
+        }
+        let additional_data = (encoding == UiAccountEncoding::JsonParsed
+            && is_known_spl_token_id(account.owner()))
+        .then(|| self.token_additional_data(account))
+        .flatten()
+        .map(|data| AccountAdditionalDataV2 {
+            spl_token_additional_data: Some(data),
+        });
+        let account = encode_ui_account(pubkey, account, encoding, additional_data, data_slice);
+        serde_json::to_value(account).map_err(RpcError::internal)
+    }
+
+    /// The mint data needed to parse the amounts of a token account.
+    fn token_additional_data(&self, account: &AccountSharedData) -> Option<SplTokenAdditionalData> {
+        let mint = get_token_account_mint(account.data())?;
+        let mint_account = self.accounts.get_account(&mint).ok()??;
+        let mint = StateWithExtensions::<Mint>::unpack(mint_account.data()).ok()?;
+        // the decoder links an older spl-token-2022 with the same layout of the extension
+        let interest_bearing_config = mint
+            .get_extension::<InterestBearingConfig>()
+            .ok()
+            .map(|config| (cast_extension(*config), self.unix_timestamp));
+        Some(SplTokenAdditionalData {
+            decimals: mint.base.decimals,
+            interest_bearing_config,
+        })
+    }
+
+    fn check_min_context_slot(&self, min_context_slot: Option<Slot>) -> RpcResult<()> {
+        match min_context_slot {
+            Some(min_context_slot) if min_context_slot > self.slot => Err(RpcError {
+                code: MIN_CONTEXT_SLOT_NOT_REACHED,
+                message: "Minimum context slot has not been reached".to_string(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn with_context(&self, value: Value) -> Value {
+        json!({"context": {"slot": self.slot}, "value": value})
+    }
+}
+
+/// Converts an extension to the same extension of another spl-token-2022 version, which has to
+/// have the same layout. Fails to compile if the sizes differ.
+fn cast_extension<Extension: Pod, Other: Pod>(extension: Extension) -> Other {
+    const { assert!(size_of::<Extension>() == size_of::<Other>()) };
+    bytemuck::cast(extension)
+}
+
+/// Name of the account index as given to `--account-index`.
+const fn account_index_name(kind: &AccountIndex) -> &'static str {
+    match kind {
+        AccountIndex::ProgramId => "program-id",
+        AccountIndex::SplTokenMint => "spl-token-mint",
+        AccountIndex::SplTokenOwner => "spl-token-owner",
+    }
+}
+
+fn parse_pubkey(param: Option<&Value>) -> RpcResult<Pubkey> {
+    param
+        .and_then(Value::as_str)
+        .and_then(|pubkey| Pubkey::from_str(pubkey).ok())
+        .ok_or_else(|| RpcError::invalid_params("Invalid param: Invalid"))
+}
+
+fn parse_config<T: Default + for<'de> Deserialize<'de>>(param: Option<&Value>) -> RpcResult<T> {
+    match param {
+        None | Some(Value::Null) => Ok(T::default()),
+        Some(config) => serde_json::from_value(config.clone())
+            .map_err(|err| RpcError::invalid_params(format!("Invalid params: {}", err))),
+    }
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "error": {"code": error.code, "message": error.message},
+        "id": id,
+    })
+}
+
+/// Serves JSON-RPC calls posted over HTTP to `address`.
+pub async fn serve(handler: Arc<RpcHandler>, address: SocketAddr) -> hyper::Result<()> {
+    let make_service = make_service_fn(move |_| {
+        let handler = Arc::clone(&handler);
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle_http(Arc::clone(&handler), request)
+            }))
+        }
+    });
+    let server = Server::try_bind(&address)?.serve(make_service);
+    info!("Serving JSON-RPC on http://{}", server.local_addr());
+    server.await
+}
+
+async fn handle_http(
+    handler: Arc<RpcHandler>,
+    request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    if request.method() != Method::POST {
+        return Ok(status_response(StatusCode::METHOD_NOT_ALLOWED));
+    }
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) => body,
+        Err(err) => {
+            warn!("Failed to read request: {}", err);
+            return Ok(status_response(StatusCode::BAD_REQUEST));
+        }
+    };
+    // lookups read the mapped accounts files and scans can take a while
+    match tokio::task::spawn_blocking(move || handler.handle_request(&body)).await {
+        Ok(response) => Ok(Response::builder()
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(response.to_string()))
+            .unwrap()),
+        Err(err) => {
+            warn!("Failed to answer request: {}", err);
+            Ok(status_response(StatusCode::INTERNAL_SERVER_ERROR))
+        }
+    }
+}
+
+fn status_response(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::archived::test_utils::*,
+        solana_runtime::snapshot_utils::ArchiveFormat,
+        solana_sdk::{account::WritableAccount, program_option::COption, system_program},
+        spl_token_2022::{
+            extension::{BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut},
+            state::AccountState,
+        },
+        std::{io::Read, path::Path},
+        tempfile::TempDir,
+    };
+
+    /// Accounts stored in the test snapshot.
+    struct Accounts {
+        program: Pubkey,
+        small: Pubkey,
+        large: Pubkey,
+        wallet: Pubkey,
+        mint: Pubkey,
+        token: Pubkey,
+        mint_2022: Pubkey,
+        token_2022: Pubkey,
+    }
+
+    fn mint_data(decimals: u8, extensions: &[ExtensionType]) -> Vec<u8> {
+        let mut data =
+            vec![0; ExtensionType::try_calculate_account_len::<Mint>(extensions).unwrap()];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        if extensions.contains(&ExtensionType::InterestBearingConfig) {
+            state.init_extension::<InterestBearingConfig>(true).unwrap();
+        }
+        state.base = Mint {
+            mint_authority: COption::None,
+            supply: 1_000_000,
+            decimals,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        };
+        state.pack_base();
+        if !extensions.is_empty() {
+            state.init_account_type().unwrap();
+        }
+        data
+    }
+
+    fn token_data(mint: Pubkey, owner: Pubkey, amount: u64) -> Vec<u8> {
+        let mut data = vec![0; Account::LEN];
+        Account {
+            mint,
+            owner,
+            amount,
+            state: AccountState::Initialized,
+            ..Account::default()
+        }
+        .pack_into_slice(&mut data);
+        data
+    }
+
+    /// Unpacks a snapshot of a bank with plain and token accounts to `dir` and opens it.
+    fn open_snapshot(dir: &Path) -> (IndexedAccounts, Accounts) {
+        let accounts = Accounts {
+            program: Pubkey::new_unique(),
+            small: Pubkey::new_unique(),
+            large: Pubkey::new_unique(),
+            wallet: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            token: Pubkey::new_unique(),
+            mint_2022: Pubkey::new_unique(),
+            token_2022: Pubkey::new_unique(),
+        };
+        let bank = new_bank();
+        let store = |pubkey, data: Vec<u8>, owner| {
+            bank.store_account(
+                pubkey,
+                &AccountSharedData::create(1_000_000, data, owner, false, 0),
+            )
+        };
+        store(&accounts.small, vec![1, 2, 3], accounts.program);
+        store(
+            &accounts.large,
+            vec![7; MAX_BASE58_BYTES + 1],
+            accounts.program,
+        );
+        store(&accounts.mint, mint_data(2, &[]), spl_token::id());
+        store(
+            &accounts.token,
+            token_data(accounts.mint, accounts.wallet, 12_345),
+            spl_token::id(),
+        );
+        store(
+            &accounts.mint_2022,
+            mint_data(3, &[ExtensionType::InterestBearingConfig]),
+            spl_token_2022::id(),
+        );
+        store(
+            &accounts.token_2022,
+            token_data(accounts.mint_2022, accounts.wallet, 5_000),
+            spl_token_2022::id(),
+        );
+        let archive = write_full_snapshot_archive(&bank, dir, ArchiveFormat::Tar);
+        let unpacked = dir.join("unpacked");
+        unpack_archive(&archive, &unpacked);
+        let indexed = IndexedAccounts::open(&unpacked, &dir.join("snapshot.index")).unwrap();
+        (indexed, accounts)
+    }
+
+    /// A handler over the test snapshot with all secondary indexes open.
+    fn handler() -> (TempDir, RpcHandler, Accounts) {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut indexed, accounts) = open_snapshot(dir.path());
+        indexed
+            .open_secondary_indexes(
+                &dir.path().join("snapshot.index"),
+                &[
+                    AccountIndex::ProgramId,
+                    AccountIndex::SplTokenOwner,
+                    AccountIndex::SplTokenMint,
+                ],
+            )
+            .unwrap();
+        (dir, RpcHandler::new(indexed).unwrap(), accounts)
+    }
+
+    fn call(handler: &RpcHandler, method: &str, params: Value) -> RpcResult<Value> {
+        handler.call(method, params.as_array().unwrap())
+    }
+
+    fn pubkeys(accounts: &Value) -> Vec<String> {
+        accounts
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|account| account["pubkey"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    fn sorted(mut pubkeys: Vec<Pubkey>) -> Vec<String> {
+        pubkeys.sort();
+        pubkeys.iter().map(Pubkey::to_string).collect()
+    }
+
+    #[test]
+    fn encodes_accounts() {
+        let (_dir, handler, accounts) = handler();
+        let small = accounts.small.to_string();
+        let large = accounts.large.to_string();
+
+        let info = call(
+            &handler,
+            "getAccountInfo",
+            json!([small, { "encoding": "base58" }]),
+        )
+        .unwrap();
+        assert_eq!(info["context"]["slot"], json!(0));
+        assert_eq!(
+            info["value"]["data"],
+            json!([bs58::encode([1, 2, 3]).into_string(), "base58"])
+        );
+        assert_eq!(info["value"]["owner"], json!(accounts.program.to_string()));
+        assert_eq!(info["value"]["lamports"], json!(1_000_000));
+
+        // base58 is limited to MAX_BASE58_BYTES, also by default, unless sliced
+        for config in [json!({ "encoding": "base58" }), Value::Null] {
+            let error = call(&handler, "getAccountInfo", json!([large, config])).unwrap_err();
+            assert_eq!(error.code, INVALID_REQUEST);
+        }
+        let sliced = call(
+            &handler,
+            "getAccountInfo",
+            json!([large, { "encoding": "base58", "dataSlice": { "offset": 1, "length": 4 } }]),
+        )
+        .unwrap();
+        assert_eq!(
+            sliced["value"]["data"][0],
+            json!(bs58::encode([7; 4]).into_string())
+        );
+
+        let info = call(
+            &handler,
+            "getAccountInfo",
+            json!([large, { "encoding": "base64" }]),
+        )
+        .unwrap();
+        assert_eq!(
+            info["value"]["data"],
+            json!([BASE64_STANDARD.encode([7; MAX_BASE58_BYTES + 1]), "base64"])
+        );
+
+        let info = call(
+            &handler,
+            "getAccountInfo",
+            json!([large, { "encoding": "base64+zstd" }]),
+        )
+        .unwrap();
+        assert_eq!(info["value"]["data"][1], json!("base64+zstd"));
+        let compressed = BASE64_STANDARD
+            .decode(info["value"]["data"][0].as_str().unwrap())
+            .unwrap();
+        let mut data = Vec::new();
+        zstd::Decoder::new(compressed.as_slice())
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(data, vec![7; MAX_BASE58_BYTES + 1]);
+
+        // getMultipleAccounts defaults to base64 and answers null for missing accounts
+        let multiple = call(
+            &handler,
+            "getMultipleAccounts",
+            json!([[large, Pubkey::new_unique().to_string()]]),
+        )
+        .unwrap();
+        assert_eq!(multiple["value"][0]["data"][1], json!("base64"));
+        assert_eq!(multiple["value"][1], Value::Null);
+        let error = call(
+            &handler,
+            "getMultipleAccounts",
+            json!([[large], { "encoding": "base58" }]),
+        )
+        .unwrap_err();
+        assert_eq!(error.code, INVALID_REQUEST);
+    }
+
+    #[test]
+    fn parses_token_accounts() {
+        let (_dir, handler, accounts) = handler();
+        let config = json!({ "encoding": "jsonParsed" });
+
+        let token = call(
+            &handler,
+            "getAccountInfo",
+            json!([accounts.token.to_string(), config]),
+        )
+        .unwrap();
+        let data = &token["value"]["data"];
+        assert_eq!(data["program"], json!("spl-token"));
+        assert_eq!(data["parsed"]["type"], json!("account"));
+        let info = &data["parsed"]["info"];
+        assert_eq!(info["owner"], json!(accounts.wallet.to_string()));
+        assert_eq!(info["mint"], json!(accounts.mint.to_string()));
+        assert_eq!(
+            info["tokenAmount"],
+            json!({ "amount": "12345", "decimals": 2, "uiAmount": 123.45, "uiAmountString": "123.45" })
+        );
+
+        // the interest-bearing mint of Token-2022 is converted for the decoder
+        let multiple = call(
+            &handler,
+            "getMultipleAccounts",
+            json!([
+                [accounts.token_2022.to_string(), accounts.small.to_string()],
+                config
+            ]),
+        )
+        .unwrap();
+        let data = &multiple["value"][0]["data"];
+        assert_eq!(data["program"], json!("spl-token-2022"));
+        assert_eq!(
+            data["parsed"]["info"]["tokenAmount"]["amount"],
+            json!("5000")
+        );
+        assert_eq!(data["parsed"]["info"]["tokenAmount"]["decimals"], json!(3));
+        assert_eq!(
+            data["parsed"]["info"]["tokenAmount"]["uiAmountString"],
+            json!("5")
+        );
+        // accounts that cannot be parsed fall back to base64
+        assert_eq!(multiple["value"][1]["data"][1], json!("base64"));
+
+        let mint = call(
+            &handler,
+            "getAccountInfo",
+            json!([accounts.mint_2022.to_string(), config]),
+        )
+        .unwrap();
+        let mint = &mint["value"]["data"]["parsed"];
+        assert_eq!(mint["type"], json!("mint"));
+        assert_eq!(
+            mint["info"]["extensions"][0]["extension"],
+            json!("interestBearingConfig")
+        );
+    }
+
+    #[test]
+    fn filters_program_accounts() {
+        let (_dir, handler, accounts) = handler();
+        let program = accounts.program.to_string();
+
+        let all = call(
+            &handler,
+            "getProgramAccounts",
+            json!([program, { "encoding": "base64" }]),
+        )
+        .unwrap();
+        assert_eq!(pubkeys(&all), sorted(vec![accounts.small, accounts.large]));
+
+        let by_size = call(
+            &handler,
+            "getProgramAccounts",
+            json!([program, { "filters": [{ "dataSize": 3 }], "withContext": true }]),
+        )
+        .unwrap();
+        assert_eq!(by_size["context"]["slot"], json!(0));
+        assert_eq!(pubkeys(&by_size["value"]), sorted(vec![accounts.small]));
+
+        let by_bytes = call(
+            &handler,
+            "getProgramAccounts",
+            json!([program, {
+                "encoding": "base64",
+                "filters": [{ "memcmp": { "offset": 1, "bytes": BASE64_STANDARD.encode([7, 7]), "encoding": "base64" } }],
+            }]),
+        )
+        .unwrap();
+        assert_eq!(pubkeys(&by_bytes), sorted(vec![accounts.large]));
+
+        // token accounts of an owner, of one program
+        let tokens = call(
+            &handler,
+            "getProgramAccounts",
+            json!([spl_token::id().to_string(), {
+                "encoding": "base64",
+                "filters": [
+                    { "dataSize": Account::LEN },
+                    { "memcmp": { "offset": 32, "bytes": accounts.wallet.to_string() } },
+                ],
+            }]),
+        )
+        .unwrap();
+        assert_eq!(pubkeys(&tokens), sorted(vec![accounts.token]));
+
+        let error = call(
+            &handler,
+            "getProgramAccounts",
+            json!([program, { "filters": vec![json!({ "dataSize": 1 }); MAX_FILTERS + 1] }]),
+        )
+        .unwrap_err();
+        assert_eq!(error.code, INVALID_PARAMS);
+    }
+
+    #[test]
+    fn refuses_queries_without_secondary_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let (indexed, accounts) = open_snapshot(dir.path());
+        let handler = RpcHandler::new(indexed).unwrap();
+
+        let error = call(
+            &handler,
+            "getProgramAccounts",
+            json!([accounts.program.to_string()]),
+        )
+        .unwrap_err();
+        assert_eq!(error.code, KEY_EXCLUDED_FROM_SECONDARY_INDEX);
+        assert!(error.message.contains("program-id"));
+
+        let error = call(
+            &handler,
+            "getTokenAccountsByOwner",
+            json!([accounts.wallet.to_string(), { "mint": accounts.mint.to_string() }]),
+        )
+        .unwrap_err();
+        assert_eq!(error.code, KEY_EXCLUDED_FROM_SECONDARY_INDEX);
+        assert!(error.message.contains("spl-token-owner"));
+
+        // lookups by pubkey need no secondary index
+        let balance = call(&handler, "getBalance", json!([accounts.small.to_string()])).unwrap();
+        assert_eq!(balance["value"], json!(1_000_000));
+    }
+
+    #[test]
+    fn gets_token_accounts_by_owner() {
+        let (_dir, handler, accounts) = handler();
+        let wallet = accounts.wallet.to_string();
+
+        let by_mint = call(
+            &handler,
+            "getTokenAccountsByOwner",
+            json!([wallet, { "mint": accounts.mint.to_string() }, { "encoding": "jsonParsed" }]),
+        )
+        .unwrap();
+        assert_eq!(pubkeys(&by_mint["value"]), sorted(vec![accounts.token]));
+        assert_eq!(
+            by_mint["value"][0]["account"]["data"]["parsed"]["info"]["tokenAmount"]["amount"],
+            json!("12345")
+        );
+
+        let by_program = call(
+            &handler,
+            "getTokenAccountsByOwner",
+            json!([wallet, { "programId": spl_token_2022::id().to_string() }, { "encoding": "base64" }]),
+        )
+        .unwrap();
+        assert_eq!(
+            pubkeys(&by_program["value"]),
+            sorted(vec![accounts.token_2022])
+        );
+
+        let unknown_mint = call(
+            &handler,
+            "getTokenAccountsByOwner",
+            json!([wallet, { "mint": accounts.small.to_string() }]),
+        )
+        .unwrap_err();
+        assert_eq!(unknown_mint.code, INVALID_PARAMS);
+        let unknown_program = call(
+            &handler,
+            "getTokenAccountsByOwner",
+            json!([wallet, { "programId": system_program::id().to_string() }]),
+        )
+        .unwrap_err();
+        assert_eq!(unknown_program.code, INVALID_PARAMS);
+    }
+
+    #[test]
+    fn checks_min_context_slot() {
+        let (_dir, handler, accounts) = handler();
+        let small = accounts.small.to_string();
+        let reached = json!({ "minContextSlot": 0 });
+        let not_reached = json!({ "minContextSlot": 1 });
+
+        assert_eq!(
+            call(&handler, "getSlot", json!([reached])).unwrap(),
+            json!(0)
+        );
+        for (method, params) in [
+            ("getSlot", json!([not_reached])),
+            ("getBalance", json!([small, not_reached])),
+            ("getAccountInfo", json!([small, not_reached])),
+            ("getMultipleAccounts", json!([[small], not_reached])),
+            (
+                "getProgramAccounts",
+                json!([accounts.program.to_string(), not_reached]),
+            ),
+            (
+                "getTokenAccountsByOwner",
+                json!([accounts.wallet.to_string(), { "mint": accounts.mint.to_string() }, not_reached]),
+            ),
+        ] {
+            let error = call(&handler, method, params).unwrap_err();
+            assert_eq!(error.code, MIN_CONTEXT_SLOT_NOT_REACHED, "{}", method);
+        }
+
+        let response = handler.handle_request(
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "getSlot", "params": [not_reached] })
+                .to_string()
+                .as_bytes(),
+        );
+        assert_eq!(response["error"]["code"], json!(-32016));
+        assert_eq!(response["id"], json!(1));
+    }
+}
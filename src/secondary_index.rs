@@ -0,0 +1,392 @@
+use {
+    crate::{
+        append_vec_iter,
+        index::{IndexEntry, PubkeyIndex},
+        token::decode_token,
+        SnapshotError, SnapshotExtractor, SnapshotResult,
+    },
+    log::info,
+    memmap2::Mmap,
+    solana_accounts_db::accounts_index::AccountIndex,
+    solana_sdk::{account::ReadableAccount, clock::Slot, pubkey::Pubkey},
+    std::{
+        fs::File,
+        io::{BufWriter, Write},
+        path::{Path, PathBuf},
+        time::Instant,
+    },
+};
+
+const SECONDARY_INDEX_MAGIC: &[u8; 8] = b"SADBSIX\0";
+const SECONDARY_INDEX_FORMAT_VERSION: u32 = 1;
+/// magic, format version, kind, snapshot slot and number of records
+const HEADER_SIZE: usize = 32;
+/// key and pubkey
+const RECORD_SIZE: usize = 64;
+
+/// A persistent index from a key of an account to the pubkeys of all accounts with that key,
+/// like the secondary indexes the validator builds with `--account-index`:
+/// - `ProgramId`: the owner of the account
+/// - `SplTokenMint`: the mint of a token account
+/// - `SplTokenOwner`: the owner of a token account
+///
+/// Records are sorted by key and pubkey, so the pubkeys of a key are found by a binary search.
+pub struct SecondaryIndex {
+    map: Mmap,
+    kind: AccountIndex,
+    slot: Slot,
+    len: usize,
+}
+
+impl SecondaryIndex {
+    /// Path of the secondary index stored next to the primary index at `index_path`.
+    pub fn path(index_path: &Path, kind: &AccountIndex) -> PathBuf {
+        let mut path = index_path.as_os_str().to_owned();
+        path.push(match kind {
+            AccountIndex::ProgramId => ".program-id",
+            AccountIndex::SplTokenMint => ".spl-token-mint",
+            AccountIndex::SplTokenOwner => ".spl-token-owner",
+        });
+        PathBuf::from(path)
+    }
+
+    /// The key of the account in an index of `kind`, if the index contains the account.
+    pub fn key(kind: &AccountIndex, account: &impl ReadableAccount) -> Option<Pubkey> {
+        match kind {
+            AccountIndex::ProgramId => Some(*account.owner()),
+            AccountIndex::SplTokenMint => decode_token(account).map(|token| token.account.mint),
+            AccountIndex::SplTokenOwner => decode_token(account).map(|token| token.account.owner),
+        }
+    }
+
+    /// Builds the secondary indexes of `kinds` for the accounts of the primary index `index`
+    /// in one pass over the accounts files, and writes them next to it at `index_path`.
+    ///
+    /// The accounts files are read again after the primary index was built, which an archive
+    /// does not allow. Use [`SecondaryIndex::build_with_index`] to build both in one pass.
+    pub fn build(
+        extractor: &mut impl SnapshotExtractor,
+        index: &PubkeyIndex,
+        index_path: &Path,
+        kinds: &[AccountIndex],
+    ) -> SnapshotResult<Vec<Self>> {
+        let started = Instant::now();
+        let mut records: Vec<Vec<(Pubkey, Pubkey)>> = vec![Vec::new(); kinds.len()];
+        for append_vec in extractor.iter() {
+            let append_vec = append_vec?;
+            for handle in append_vec_iter(&append_vec) {
+                let Some(stored) = handle.access() else {
+                    continue;
+                };
+                // only the latest copy the primary index points to
+                let entry = IndexEntry {
+                    slot: append_vec.slot(),
+                    append_vec_id: append_vec.id(),
+                    offset: stored.offset as u64,
+                };
+                if index.get(&stored.meta.pubkey) != Some(entry) {
+                    continue;
+                }
+                for (kind, records) in kinds.iter().zip(records.iter_mut()) {
+                    if let Some(key) = Self::key(kind, &stored) {
+                        records.push((key, stored.meta.pubkey));
+                    }
+                }
+            }
+        }
+        Self::write_all(index_path, kinds, index.slot(), records, started)
+    }
+
+    /// Builds the primary index at `index_path` and the secondary indexes of `kinds` next to
+    /// it in a single pass over the accounts files, as needed for an archive.
+    ///
+    /// The keys of every stored copy are collected while the latest copies are resolved, and
+    /// the superseded ones are dropped once the primary index is written. This needs about 100
+    /// bytes per stored copy with a key on top of what [`PubkeyIndex::build`] needs.
+    pub fn build_with_index(
+        extractor: &mut impl SnapshotExtractor,
+        index_path: &Path,
+        kinds: &[AccountIndex],
+    ) -> SnapshotResult<(PubkeyIndex, Vec<Self>)> {
+        let started = Instant::now();
+        let mut copies: Vec<Vec<(Pubkey, Pubkey, IndexEntry)>> = vec![Vec::new(); kinds.len()];
+        let index = PubkeyIndex::build_with(extractor, index_path, |position, stored| {
+            for (kind, copies) in kinds.iter().zip(copies.iter_mut()) {
+                if let Some(key) = Self::key(kind, stored) {
+                    copies.push((key, stored.meta.pubkey, IndexEntry::from(position)));
+                }
+            }
+        })?;
+        let records = copies
+            .into_iter()
+            .map(|copies| {
+                // only the latest copy the primary index points to
+                copies
+                    .into_iter()
+                    .filter(|(_, pubkey, entry)| index.get(pubkey) == Some(*entry))
+                    .map(|(key, pubkey, _)| (key, pubkey))
+                    .collect()
+            })
+            .collect();
+        let indexes = Self::write_all(index_path, kinds, index.slot(), records, started)?;
+        Ok((index, indexes))
+    }
+
+    /// Sorts and writes the records of each index of `kinds` and opens the written indexes.
+    fn write_all(
+        index_path: &Path,
+        kinds: &[AccountIndex],
+        slot: Slot,
+        records: Vec<Vec<(Pubkey, Pubkey)>>,
+        started: Instant,
+    ) -> SnapshotResult<Vec<Self>> {
+        let indexes = kinds
+            .iter()
+            .zip(records)
+            .map(|(kind, mut records)| {
+                records.sort_unstable();
+                let path = Self::path(index_path, kind);
+                Self::write(&path, kind, slot, &records)?;
+                Self::open(&path)
+            })
+            .collect::<SnapshotResult<Vec<_>>>()?;
+        for secondary_index in &indexes {
+            info!(
+                "Wrote {:?} index of {} accounts to {}",
+                secondary_index.kind(),
+                secondary_index.len(),
+                Self::path(index_path, secondary_index.kind()).display()
+            );
+        }
+        info!(
+            "Built {} secondary indexes in {:?}",
+            indexes.len(),
+            started.elapsed()
+        );
+        Ok(indexes)
+    }
+
+    fn write(
+        path: &Path,
+        kind: &AccountIndex,
+        slot: Slot,
+        records: &[(Pubkey, Pubkey)],
+    ) -> SnapshotResult<()> {
+        // Write to a temporary file first so an interrupted build leaves no partial index.
+        let directory = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty());
+        let file = tempfile::NamedTempFile::new_in(directory.unwrap_or(Path::new(".")))?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(SECONDARY_INDEX_MAGIC)?;
+        writer.write_all(&SECONDARY_INDEX_FORMAT_VERSION.to_le_bytes())?;
+        writer.write_all(&kind_id(kind).to_le_bytes())?;
+        writer.write_all(&slot.to_le_bytes())?;
+        writer.write_all(&(records.len() as u64).to_le_bytes())?;
+        for (key, pubkey) in records {
+            writer.write_all(key.as_ref())?;
+            writer.write_all(pubkey.as_ref())?;
+        }
+        let file = writer.into_inner().map_err(|err| err.into_error())?;
+        file.persist(path).map_err(|err| err.error)?;
+        Ok(())
+    }
+
+    /// Opens a secondary index file written by [`SecondaryIndex::build`].
+    pub fn open<P: AsRef<Path>>(path: P) -> SnapshotResult<Self> {
+        let file = File::open(path)?;
+        let map = unsafe { Mmap::map(&file)? };
+        if map.len() < HEADER_SIZE || &map[..8] != SECONDARY_INDEX_MAGIC {
+            return Err(SnapshotError::InvalidIndex(
+                "not a secondary index file".to_string(),
+            ));
+        }
+        let field = |start: usize| u32::from_le_bytes(map[start..start + 4].try_into().unwrap());
+        let version = field(8);
+        if version != SECONDARY_INDEX_FORMAT_VERSION {
+            return Err(SnapshotError::InvalidIndex(format!(
+                "unsupported format version {}",
+                version
+            )));
+        }
+        let kind = match field(12) {
+            0 => AccountIndex::ProgramId,
+            1 => AccountIndex::SplTokenMint,
+            2 => AccountIndex::SplTokenOwner,
+            kind => {
+                return Err(SnapshotError::InvalidIndex(format!(
+                    "unknown index kind {}",
+                    kind
+                )))
+            }
+        };
+        let slot = u64::from_le_bytes(map[16..24].try_into().unwrap());
+        let len = u64::from_le_bytes(map[24..32].try_into().unwrap()) as usize;
+        if len
+            .checked_mul(RECORD_SIZE)
+            .and_then(|size| size.checked_add(HEADER_SIZE))
+            != Some(map.len())
+        {
+            return Err(SnapshotError::InvalidIndex(format!(
+                "{} records do not fit {} bytes",
+                len,
+                map.len()
+            )));
+        }
+        Ok(SecondaryIndex {
+            map,
+            kind,
+            slot,
+            len,
+        })
+    }
+
+    pub const fn kind(&self) -> &AccountIndex {
+        &self.kind
+    }
+
+    /// Slot of the snapshot the index was built from.
+    pub const fn slot(&self) -> Slot {
+        self.slot
+    }
+
+    /// Number of (key, pubkey) records.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Pubkeys of all accounts with `key`, sorted.
+    pub fn get(&self, key: &Pubkey) -> impl Iterator<Item = Pubkey> + '_ {
+        // first record with a key not less than `key`
+        let (mut low, mut high) = (0, self.len);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if self.record(middle)[..32] < *key.as_ref() {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        let key = *key;
+        (low..self.len)
+            .map(|index| self.record(index))
+            .take_while(move |record| record[..32] == *key.as_ref())
+            .map(|record| Pubkey::try_from(&record[32..]).unwrap())
+    }
+
+    fn record(&self, index: usize) -> &[u8] {
+        let start = HEADER_SIZE + index * RECORD_SIZE;
+        &self.map[start..start + RECORD_SIZE]
+    }
+}
+
+const fn kind_id(kind: &AccountIndex) -> u32 {
+    match kind {
+        AccountIndex::ProgramId => 0,
+        AccountIndex::SplTokenMint => 1,
+        AccountIndex::SplTokenOwner => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{archived::test_utils::*, incremental::IncrementalSnapshotExtractor},
+        solana_runtime::snapshot_utils::ArchiveFormat,
+        solana_sdk::{account::AccountSharedData, program_pack::Pack},
+        spl_token::state::{Account as TokenAccount, AccountState},
+        std::collections::BTreeSet,
+    };
+
+    const FORMAT: ArchiveFormat = ArchiveFormat::Tar;
+
+    fn token_account(mint: Pubkey, wallet: Pubkey) -> AccountSharedData {
+        let mut data = vec![0; TokenAccount::LEN];
+        TokenAccount {
+            mint,
+            owner: wallet,
+            amount: 42,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        }
+        .pack_into_slice(&mut data);
+        let mut account = AccountSharedData::new(2_000_000, data.len(), &spl_token::id());
+        account.set_data_from_slice(&data);
+        account
+    }
+
+    #[test]
+    fn builds_with_index_from_latest_copies() {
+        let dir = tempfile::tempdir().unwrap();
+        let (owner, new_owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (wallet, new_wallet) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (mint, new_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let bank = new_bank();
+        let pubkeys: Vec<_> = (0..10).map(|_| Pubkey::new_unique()).collect();
+        for (i, pubkey) in pubkeys.iter().enumerate() {
+            bank.store_account(pubkey, &AccountSharedData::new(1000 + i as u64, i, &owner));
+        }
+        let token_pubkey = Pubkey::new_unique();
+        bank.store_account(&token_pubkey, &token_account(mint, wallet));
+        let full_archive = write_full_snapshot_archive(&bank, dir.path(), FORMAT);
+        // an incremental snapshot moves an account to another owner and the token account to
+        // another wallet and mint, while the full snapshot holds the old copies
+        let bank = new_child_bank(bank, 1);
+        bank.store_account(&pubkeys[0], &AccountSharedData::new(5000, 0, &new_owner));
+        bank.store_account(&token_pubkey, &token_account(new_mint, new_wallet));
+        let incremental_archive = write_incremental_snapshot_archive(&bank, 0, dir.path(), FORMAT);
+
+        let index_path = dir.path().join("snapshot.index");
+        let mut extractor =
+            IncrementalSnapshotExtractor::open(&full_archive, &incremental_archive).unwrap();
+        let kinds = [
+            AccountIndex::ProgramId,
+            AccountIndex::SplTokenOwner,
+            AccountIndex::SplTokenMint,
+        ];
+        let (index, indexes) =
+            SecondaryIndex::build_with_index(&mut extractor, &index_path, &kinds).unwrap();
+        assert!(!index.is_empty());
+        assert_eq!(indexes.len(), 3);
+
+        let program_id = &indexes[0];
+        assert_eq!(program_id.kind(), &AccountIndex::ProgramId);
+        assert_eq!(program_id.slot(), index.slot());
+        assert_eq!(program_id.len(), index.len());
+        let owned: Vec<_> = program_id.get(&owner).collect();
+        let mut expected = pubkeys[1..].to_vec();
+        expected.sort();
+        assert_eq!(owned, expected);
+        assert_eq!(
+            program_id.get(&new_owner).collect::<Vec<_>>(),
+            vec![pubkeys[0]]
+        );
+        // every account is indexed once, by its latest copy
+        let indexed: BTreeSet<_> = index.iter().map(|(pubkey, _)| pubkey).collect();
+        assert!(pubkeys.iter().all(|pubkey| indexed.contains(pubkey)));
+
+        let token_owner = &indexes[1];
+        assert_eq!(token_owner.kind(), &AccountIndex::SplTokenOwner);
+        assert_eq!(token_owner.get(&wallet).count(), 0);
+        assert_eq!(
+            token_owner.get(&new_wallet).collect::<Vec<_>>(),
+            vec![token_pubkey]
+        );
+        assert_eq!(token_owner.len(), 1);
+
+        let token_mint = &indexes[2];
+        assert_eq!(token_mint.get(&mint).count(), 0);
+        assert_eq!(
+            token_mint.get(&new_mint).collect::<Vec<_>>(),
+            vec![token_pubkey]
+        );
+
+        let reopened = SecondaryIndex::open(SecondaryIndex::path(&index_path, &kinds[0])).unwrap();
+        assert_eq!(reopened.get(&owner).collect::<Vec<_>>(), owned);
+    }
+}
@@ -15,15 +15,15 @@
 // This file contains code vendored from https://github.com/solana-labs/solana
 
 use bincode::Options;
-use serde::de::DeserializeOwned;
+use serde::de::{DeserializeOwned, Deserializer, SeqAccess, Visitor};
 use serde::{Deserialize, Serialize};
 use solana_frozen_abi_macro::AbiExample;
 use solana_accounts_db::account_storage::meta::StoredMetaWriteVersion;
 use solana_accounts_db::ancestors::AncestorsForSerialization;
 use solana_accounts_db::blockhash_queue::BlockhashQueue;
-use solana_runtime::epoch_stakes::EpochStakes;
+use solana_runtime::epoch_stakes::{EpochStakes, VersionedEpochStakes};
+use solana_runtime::serde_snapshot::BankIncrementalSnapshotPersistence;
 use solana_sdk::rent_collector::RentCollector;
-use solana_runtime::stakes::Stakes;
 use solana_sdk::clock::{Epoch, UnixTimestamp};
 use solana_sdk::deserialize_utils::default_on_eof;
 use solana_sdk::epoch_schedule::EpochSchedule;
@@ -34,9 +34,12 @@ use solana_sdk::inflation::Inflation;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::slot_history::Slot;
 use solana_sdk::stake::state::Delegation;
+use solana_sdk::stake_history::StakeHistory;
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::io::Read;
 use solana_accounts_db::accounts_db::stats::BankHashStats;
+use solana_vote::vote_account::VoteAccounts;
 
 const MAX_STREAM_SIZE: u64 = 32 * 1024 * 1024 * 1024;
 
@@ -59,6 +62,20 @@ struct UnusedAccounts {
     unused3: HashMap<Pubkey, u64>,
 }
 
+/// The stakes cache of the bank, in the layout of `solana_runtime::stakes::Stakes<Delegation>`
+/// which does not expose the stake delegations.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct DeserializableStakes {
+    /// vote accounts with the activated stake delegated to them at `epoch`
+    pub vote_accounts: VoteAccounts,
+    /// delegations of all stake accounts, by stake account
+    pub stake_delegations: HashMap<Pubkey, Delegation>,
+    #[allow(dead_code)]
+    unused: u64,
+    pub epoch: Epoch,
+    pub stake_history: StakeHistory,
+}
+
 #[derive(Deserialize)]
 #[allow(dead_code)]
 pub struct DeserializableVersionedBank {
@@ -90,7 +107,7 @@ pub struct DeserializableVersionedBank {
     pub rent_collector: RentCollector,
     pub epoch_schedule: EpochSchedule,
     pub inflation: Inflation,
-    pub stakes: Stakes<Delegation>,
+    pub stakes: DeserializableStakes,
     #[allow(dead_code)]
     unused_accounts: UnusedAccounts,
     pub epoch_stakes: HashMap<Epoch, EpochStakes>,
@@ -118,6 +135,34 @@ pub struct AccountsDbFields<T>(
     pub Vec<(Slot, Hash)>,
 );
 
+impl<T> AccountsDbFields<T> {
+    pub const fn storages(&self) -> &HashMap<Slot, Vec<T>> {
+        &self.0
+    }
+
+    pub const fn write_version(&self) -> StoredMetaWriteVersion {
+        self.1
+    }
+
+    pub const fn slot(&self) -> Slot {
+        self.2
+    }
+
+    pub const fn bank_hash_info(&self) -> &BankHashInfo {
+        &self.3
+    }
+
+    /// all slots that were roots within the last epoch
+    pub const fn historical_roots(&self) -> &Vec<Slot> {
+        &self.4
+    }
+
+    /// slots that were roots within the last epoch for which we care about the hash value
+    pub const fn historical_roots_with_hash(&self) -> &Vec<(Slot, Hash)> {
+        &self.5
+    }
+}
+
 pub type SerializedAppendVecId = usize;
 
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize)]
@@ -125,3 +170,72 @@ pub struct SerializableAccountStorageEntry {
     pub id: SerializedAppendVecId,
     pub accounts_current_len: usize,
 }
+
+/// Fields appended to the snapshot manifest after the `AccountsDbFields` by newer validators.
+/// Each of them falls back to its default when the manifest ends before it.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ExtraFieldsToDeserialize {
+    #[serde(deserialize_with = "default_on_eof")]
+    pub lamports_per_signature: u64,
+    #[serde(deserialize_with = "default_on_eof")]
+    pub incremental_snapshot_persistence: Option<BankIncrementalSnapshotPersistence>,
+    #[serde(deserialize_with = "default_on_eof")]
+    pub epoch_accounts_hash: Option<Hash>,
+    #[serde(deserialize_with = "default_on_eof")]
+    pub versioned_epoch_stakes: HashMap<u64, VersionedEpochStakes>,
+    #[serde(deserialize_with = "default_on_eof")]
+    pub accounts_lt_hash: Option<SerdeAccountsLtHash>,
+}
+
+impl ExtraFieldsToDeserialize {
+    /// Stakes of `epoch`, preferring the versioned epoch stakes over the ones in the bank fields.
+    pub fn epoch_stakes(
+        &self,
+        bank_fields: &DeserializableVersionedBank,
+        epoch: Epoch,
+    ) -> Option<EpochStakes> {
+        self.versioned_epoch_stakes
+            .get(&epoch)
+            .cloned()
+            .map(EpochStakes::from)
+            .or_else(|| bank_fields.epoch_stakes.get(&epoch).cloned())
+    }
+}
+
+pub const LT_HASH_NUM_ELEMENTS: usize = 1024;
+
+/// The accounts lattice hash, as stored in the snapshot manifest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SerdeAccountsLtHash(pub [u16; LT_HASH_NUM_ELEMENTS]);
+
+impl<'de> Deserialize<'de> for SerdeAccountsLtHash {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct LtHashVisitor;
+
+        impl<'de> Visitor<'de> for LtHashVisitor {
+            type Value = SerdeAccountsLtHash;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(formatter, "an array of {} u16", LT_HASH_NUM_ELEMENTS)
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut elements = [0u16; LT_HASH_NUM_ELEMENTS];
+                for (i, element) in elements.iter_mut().enumerate() {
+                    *element = seq
+                        .next_element()?
+                        .ok_or_else(|| serde::de::Error::invalid_length(i, &self))?;
+                }
+                Ok(SerdeAccountsLtHash(elements))
+            }
+        }
+
+        deserializer.deserialize_tuple(LT_HASH_NUM_ELEMENTS, LtHashVisitor)
+    }
+}
@@ -0,0 +1,339 @@
+use {
+    crate::{latest::LatestAccounts, solana::DeserializableStakes},
+    log::info,
+    solana_sdk::{
+        account::ReadableAccount,
+        clock::Epoch,
+        epoch_schedule::EpochSchedule,
+        feature::Feature,
+        feature_set,
+        pubkey::Pubkey,
+        stake::{self, state::StakeStateV2},
+        stake_history::StakeHistory,
+        vote::{
+            self,
+            state::{VoteState, VoteStateVersions},
+        },
+    },
+    std::{cmp::Reverse, collections::HashMap, fmt, time::Instant},
+};
+
+/// Decodes an account owned by the stake program.
+pub fn decode_stake_account(account: &impl ReadableAccount) -> Option<StakeStateV2> {
+    if *account.owner() != stake::program::id() {
+        return None;
+    }
+    bincode::deserialize(account.data()).ok()
+}
+
+/// Decodes an initialized vote account of any version, converted to the current version.
+pub fn decode_vote_account(account: &impl ReadableAccount) -> Option<VoteState> {
+    if *account.owner() != vote::program::id() {
+        return None;
+    }
+    let versions: VoteStateVersions = bincode::deserialize(account.data()).ok()?;
+    (!versions.is_uninitialized()).then(|| versions.convert_to_current())
+}
+
+/// The epoch from which the reduced stake warmup and cooldown rate applies, if the feature is
+/// active in the account set.
+pub fn new_rate_activation_epoch(
+    accounts: &LatestAccounts,
+    epoch_schedule: &EpochSchedule,
+) -> Option<Epoch> {
+    let feature = accounts.get(&feature_set::reduce_stake_warmup_cooldown::id())?;
+    let feature: Feature = bincode::deserialize(feature.data()).ok()?;
+    feature
+        .activated_at
+        .map(|slot| epoch_schedule.get_epoch(slot))
+}
+
+/// The stake delegated to a vote account.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidatorStake {
+    pub vote_account: Pubkey,
+    /// identity of the validator
+    pub node_pubkey: Pubkey,
+    pub commission: u8,
+    /// effective stake of all delegations at the epoch of the report
+    pub activated_stake: u64,
+    /// number of stake accounts with effective stake
+    pub delegators: usize,
+}
+
+/// Computes the effective stake of every vote account at `epoch` from the stake accounts.
+/// Validators are sorted by descending stake, ties by vote account. Vote accounts without
+/// stake are included, delegations to accounts that are not valid vote accounts are not.
+pub fn delegation_report(
+    accounts: &LatestAccounts,
+    epoch: Epoch,
+    stake_history: &StakeHistory,
+    new_rate_activation_epoch: Option<Epoch>,
+) -> Vec<ValidatorStake> {
+    let started = Instant::now();
+    let mut validators: HashMap<Pubkey, ValidatorStake> = HashMap::new();
+    let mut delegations = Vec::new();
+    for (_, handle) in accounts.iter() {
+        let Some(stored) = handle.access() else {
+            continue;
+        };
+        if let Some(vote_state) = decode_vote_account(&stored) {
+            validators.insert(
+                stored.meta.pubkey,
+                ValidatorStake {
+                    vote_account: stored.meta.pubkey,
+                    node_pubkey: vote_state.node_pubkey,
+                    commission: vote_state.commission,
+                    activated_stake: 0,
+                    delegators: 0,
+                },
+            );
+        } else if let Some(stake) = decode_stake_account(&stored).and_then(|state| state.stake()) {
+            delegations.push(stake.delegation);
+        }
+    }
+
+    for delegation in &delegations {
+        let Some(validator) = validators.get_mut(&delegation.voter_pubkey) else {
+            continue;
+        };
+        let stake = delegation.stake(epoch, stake_history, new_rate_activation_epoch);
+        if stake > 0 {
+            validator.activated_stake += stake;
+            validator.delegators += 1;
+        }
+    }
+
+    let mut validators: Vec<ValidatorStake> = validators.into_values().collect();
+    validators.sort_unstable_by_key(|validator| {
+        (Reverse(validator.activated_stake), validator.vote_account)
+    });
+    info!(
+        "Computed stake of {} vote accounts from {} delegations in {:?}",
+        validators.len(),
+        delegations.len(),
+        started.elapsed()
+    );
+    validators
+}
+
+/// A vote account whose computed stake differs from the stakes cache of the bank.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StakeMismatch {
+    pub vote_account: Pubkey,
+    pub activated_stake: u64,
+    pub bank_stake: u64,
+}
+
+impl fmt::Display for StakeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "vote account {} has activated stake {} but {} in the bank",
+            self.vote_account, self.activated_stake, self.bank_stake
+        )
+    }
+}
+
+/// Compares the stake of every vote account with the stakes cache of the bank, which holds the
+/// stake at `stakes.epoch`. Vote accounts without stake on both sides are not compared.
+pub fn compare_with_bank(
+    validators: &[ValidatorStake],
+    stakes: &DeserializableStakes,
+) -> Vec<StakeMismatch> {
+    let activated_stakes: HashMap<Pubkey, u64> = validators
+        .iter()
+        .map(|validator| (validator.vote_account, validator.activated_stake))
+        .collect();
+    let bank_stakes: HashMap<Pubkey, u64> = stakes
+        .vote_accounts
+        .delegated_stakes()
+        .map(|(vote_account, stake)| (*vote_account, stake))
+        .collect();
+
+    let mut mismatches: Vec<StakeMismatch> = activated_stakes
+        .keys()
+        .chain(bank_stakes.keys())
+        .filter_map(|vote_account| {
+            let activated_stake = activated_stakes.get(vote_account).copied().unwrap_or(0);
+            let bank_stake = bank_stakes.get(vote_account).copied().unwrap_or(0);
+            (activated_stake != bank_stake).then_some(StakeMismatch {
+                vote_account: *vote_account,
+                activated_stake,
+                bank_stake,
+            })
+        })
+        .collect();
+    mismatches.sort_unstable_by_key(|mismatch| mismatch.vote_account);
+    mismatches.dedup();
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{append_vec::test_utils::TestAccount, test_utils::TestExtractor},
+        solana_sdk::{
+            account::{AccountSharedData, WritableAccount},
+            stake::{
+                stake_flags::StakeFlags,
+                state::{Delegation, Meta, Stake},
+            },
+            stake_history::StakeHistoryEntry,
+            vote::state::VoteInit,
+        },
+        solana_vote::vote_account::{VoteAccount, VoteAccounts},
+        std::sync::Arc,
+    };
+
+    const EPOCH: Epoch = 11;
+
+    fn vote_account(commission: u8) -> AccountSharedData {
+        let vote_state = VoteState::new(
+            &VoteInit {
+                node_pubkey: Pubkey::new_unique(),
+                commission,
+                ..VoteInit::default()
+            },
+            &Default::default(),
+        );
+        let mut data = vec![0; VoteState::size_of()];
+        VoteState::serialize(&VoteStateVersions::new_current(vote_state), &mut data).unwrap();
+        AccountSharedData::create(1, data, vote::program::id(), false, 0)
+    }
+
+    fn stake_account(
+        voter_pubkey: Pubkey,
+        stake: u64,
+        activation_epoch: Epoch,
+        deactivation_epoch: Epoch,
+    ) -> AccountSharedData {
+        let state = StakeStateV2::Stake(
+            Meta::default(),
+            Stake {
+                delegation: Delegation {
+                    voter_pubkey,
+                    stake,
+                    activation_epoch,
+                    deactivation_epoch,
+                    ..Delegation::default()
+                },
+                credits_observed: 0,
+            },
+            StakeFlags::empty(),
+        );
+        let data = bincode::serialize(&state).unwrap();
+        AccountSharedData::create(1, data, stake::program::id(), false, 0)
+    }
+
+    /// One cluster-wide epoch of warmup and cooldown before `EPOCH`.
+    fn stake_history() -> StakeHistory {
+        let mut stake_history = StakeHistory::default();
+        stake_history.add(
+            EPOCH - 1,
+            StakeHistoryEntry {
+                effective: 200,
+                activating: 100,
+                deactivating: 400,
+            },
+        );
+        stake_history
+    }
+
+    /// Three vote accounts and the stake delegated to them, with their pubkeys.
+    fn accounts() -> (LatestAccounts, [(Pubkey, AccountSharedData); 3]) {
+        let votes = [0, 1, 2].map(|commission| (Pubkey::new_unique(), vote_account(commission)));
+        let [(first, _), (second, _), _] = &votes;
+        let bootstrap = u64::MAX;
+        let stakes = [
+            stake_account(*first, 1_000, bootstrap, u64::MAX),
+            // activating in the previous epoch and the current epoch
+            stake_account(*first, 100, EPOCH - 1, u64::MAX),
+            stake_account(*first, 100, EPOCH, u64::MAX),
+            // deactivating in the previous epoch
+            stake_account(*second, 400, bootstrap, EPOCH - 1),
+            // delegated to an account that is not a vote account
+            stake_account(Pubkey::new_unique(), 100, bootstrap, u64::MAX),
+        ];
+        let test_accounts = votes
+            .iter()
+            .map(|(pubkey, account)| TestAccount::new(*pubkey, account.clone()))
+            .chain(
+                stakes
+                    .into_iter()
+                    .map(|account| TestAccount::new(Pubkey::new_unique(), account)),
+            )
+            .collect();
+        let mut extractor = TestExtractor::new(vec![(1, 1, test_accounts)]);
+        (
+            LatestAccounts::from_extractor(&mut extractor).unwrap(),
+            votes,
+        )
+    }
+
+    #[test]
+    fn reports_effective_stake_with_history() {
+        let (accounts, [(first, _), (second, _), (third, _)]) = accounts();
+        let report = delegation_report(&accounts, EPOCH, &stake_history(), None);
+        let stakes: Vec<_> = report
+            .iter()
+            .map(|validator| {
+                (
+                    validator.vote_account,
+                    validator.commission,
+                    validator.activated_stake,
+                    validator.delegators,
+                )
+            })
+            .collect();
+        // a quarter of the cluster stake warms up and cools down per epoch
+        assert_eq!(
+            stakes,
+            vec![(first, 0, 1_050, 2), (second, 1, 350, 1), (third, 2, 0, 0)]
+        );
+
+        // the reduced rate of 9%
+        let report = delegation_report(&accounts, EPOCH, &stake_history(), Some(0));
+        let stakes: Vec<_> = report
+            .iter()
+            .map(|validator| validator.activated_stake)
+            .collect();
+        assert_eq!(stakes, vec![1_018, 382, 0]);
+    }
+
+    #[test]
+    fn compares_with_stakes_cache() {
+        let (accounts, [(first, first_account), (second, second_account), _]) = accounts();
+        let report = delegation_report(&accounts, EPOCH, &stake_history(), None);
+        let unknown = Pubkey::new_unique();
+        let vote_accounts = [
+            (first, (1_050, first_account)),
+            (second, (400, second_account)),
+            (unknown, (5, vote_account(0))),
+        ]
+        .into_iter()
+        .map(|(pubkey, (stake, account))| {
+            (pubkey, (stake, VoteAccount::try_from(account).unwrap()))
+        })
+        .collect::<HashMap<_, _>>();
+        let mut stakes = DeserializableStakes::default();
+        stakes.vote_accounts = VoteAccounts::from(Arc::new(vote_accounts));
+        stakes.epoch = EPOCH;
+
+        let mut expected = vec![
+            StakeMismatch {
+                vote_account: second,
+                activated_stake: 350,
+                bank_stake: 400,
+            },
+            StakeMismatch {
+                vote_account: unknown,
+                activated_stake: 0,
+                bank_stake: 5,
+            },
+        ];
+        expected.sort_unstable_by_key(|mismatch| mismatch.vote_account);
+        assert_eq!(compare_with_bank(&report, &stakes), expected);
+    }
+}
@@ -0,0 +1,289 @@
+use {
+    crate::{
+        append_vec::StoredAccountMeta,
+        filter::AccountFilters,
+        latest::{AccountPosition, LatestAccounts, VersionKey},
+        solana::DeserializableVersionedBank,
+        SnapshotExtractor, SnapshotResult,
+    },
+    serde::de::DeserializeOwned,
+    solana_sdk::{
+        clock::{Clock, Slot},
+        epoch_rewards::EpochRewards,
+        epoch_schedule::EpochSchedule,
+        pubkey::Pubkey,
+        rent::Rent,
+        slot_hashes::SlotHashes,
+        slot_history::SlotHistory,
+        stake_history::StakeHistory,
+        sysvar::{self, last_restart_slot::LastRestartSlot},
+    },
+    std::{collections::HashMap, fmt},
+};
+
+/// The sysvar accounts of a snapshot, decoded from their latest copy. A sysvar is None if its
+/// account does not exist or cannot be decoded.
+#[derive(Debug, Default, PartialEq)]
+pub struct Sysvars {
+    pub clock: Option<Clock>,
+    pub epoch_rewards: Option<EpochRewards>,
+    pub epoch_schedule: Option<EpochSchedule>,
+    pub last_restart_slot: Option<LastRestartSlot>,
+    pub rent: Option<Rent>,
+    pub slot_hashes: Option<SlotHashes>,
+    pub slot_history: Option<SlotHistory>,
+    pub stake_history: Option<StakeHistory>,
+}
+
+/// Pubkeys of the sysvars decoded by [`Sysvars`].
+pub const SYSVAR_IDS: [Pubkey; 8] = [
+    sysvar::clock::ID,
+    sysvar::epoch_rewards::ID,
+    sysvar::epoch_schedule::ID,
+    sysvar::last_restart_slot::ID,
+    sysvar::rent::ID,
+    sysvar::slot_hashes::ID,
+    sysvar::slot_history::ID,
+    sysvar::stake_history::ID,
+];
+
+impl Sysvars {
+    /// Reads the sysvars from all accounts files of the extractor, picking the latest copy the
+    /// way [`LatestPositions`](crate::latest::LatestPositions) does.
+    pub fn from_extractor(extractor: &mut impl SnapshotExtractor) -> SnapshotResult<Self> {
+        let filters = AccountFilters::new().with_pubkeys(SYSVAR_IDS);
+        // version key and data of the latest copy of each sysvar
+        let mut latest: HashMap<Pubkey, (VersionKey, Option<Vec<u8>>)> = HashMap::new();
+        for append_vec in extractor.iter() {
+            let append_vec = append_vec?;
+            for stored in filters.iter(&append_vec) {
+                let position = AccountPosition {
+                    slot: append_vec.slot(),
+                    append_vec_id: append_vec.id(),
+                    offset: stored.offset,
+                };
+                let key = position.version_key(stored.meta.write_version_obsolete);
+                if latest
+                    .get(&stored.meta.pubkey)
+                    .is_none_or(|(latest_key, _)| key > *latest_key)
+                {
+                    let data = (stored.account_meta.lamports != 0).then(|| stored.data.to_vec());
+                    latest.insert(stored.meta.pubkey, (key, data));
+                }
+            }
+        }
+        Ok(Self::from_data(|pubkey| {
+            latest.get(pubkey).and_then(|(_, data)| data.as_deref())
+        }))
+    }
+
+    /// Reads the sysvars from the latest copy of each account.
+    pub fn from_accounts(accounts: &LatestAccounts) -> Self {
+        let stored: HashMap<Pubkey, StoredAccountMeta> = SYSVAR_IDS
+            .iter()
+            .filter_map(|pubkey| Some((*pubkey, accounts.get(pubkey)?)))
+            .collect();
+        Self::from_data(|pubkey| stored.get(pubkey).map(|stored| stored.data))
+    }
+
+    fn from_data<'a>(data: impl Fn(&Pubkey) -> Option<&'a [u8]>) -> Self {
+        fn decode<T: DeserializeOwned>(data: Option<&[u8]>) -> Option<T> {
+            bincode::deserialize(data?).ok()
+        }
+
+        Sysvars {
+            clock: decode(data(&sysvar::clock::ID)),
+            epoch_rewards: decode(data(&sysvar::epoch_rewards::ID)),
+            epoch_schedule: decode(data(&sysvar::epoch_schedule::ID)),
+            last_restart_slot: decode(data(&sysvar::last_restart_slot::ID)),
+            rent: decode(data(&sysvar::rent::ID)),
+            slot_hashes: decode(data(&sysvar::slot_hashes::ID)),
+            slot_history: decode(data(&sysvar::slot_history::ID)),
+            stake_history: decode(data(&sysvar::stake_history::ID)),
+        }
+    }
+
+    /// Compares the Clock and EpochSchedule sysvars with the bank fields of the manifest.
+    pub fn verify(&self, bank_fields: &DeserializableVersionedBank) -> Vec<SysvarMismatch> {
+        let mut mismatches = Vec::new();
+        match &self.clock {
+            Some(clock) if clock.slot != bank_fields.slot => {
+                mismatches.push(SysvarMismatch::ClockSlot {
+                    sysvar: clock.slot,
+                    manifest: bank_fields.slot,
+                })
+            }
+            Some(_) => {}
+            None => mismatches.push(SysvarMismatch::Missing(sysvar::clock::ID)),
+        }
+        match &self.epoch_schedule {
+            Some(epoch_schedule) if *epoch_schedule != bank_fields.epoch_schedule => mismatches
+                .push(SysvarMismatch::EpochSchedule {
+                    sysvar: epoch_schedule.clone(),
+                    manifest: bank_fields.epoch_schedule.clone(),
+                }),
+            Some(_) => {}
+            None => mismatches.push(SysvarMismatch::Missing(sysvar::epoch_schedule::ID)),
+        }
+        mismatches
+    }
+}
+
+/// A sysvar that does not agree with the snapshot manifest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SysvarMismatch {
+    ClockSlot {
+        sysvar: Slot,
+        manifest: Slot,
+    },
+    EpochSchedule {
+        sysvar: EpochSchedule,
+        manifest: EpochSchedule,
+    },
+    /// The sysvar account is missing or cannot be decoded.
+    Missing(Pubkey),
+}
+
+impl fmt::Display for SysvarMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SysvarMismatch::ClockSlot { sysvar, manifest } => write!(
+                f,
+                "Clock sysvar is at slot {} but the manifest at slot {}",
+                sysvar, manifest
+            ),
+            SysvarMismatch::EpochSchedule { sysvar, manifest } => write!(
+                f,
+                "EpochSchedule sysvar {:?} differs from the manifest {:?}",
+                sysvar, manifest
+            ),
+            SysvarMismatch::Missing(pubkey) => write!(f, "sysvar {} is missing", pubkey),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            append_vec::test_utils::TestAccount, archived::test_utils::*,
+            archived::ArchiveSnapshotExtractor, test_utils::TestExtractor,
+        },
+        serde::Serialize,
+        solana_runtime::snapshot_utils::ArchiveFormat,
+        solana_sdk::{
+            account::{AccountSharedData, WritableAccount},
+            stake_history::StakeHistoryEntry,
+        },
+    };
+
+    fn sysvar(pubkey: Pubkey, lamports: u64, value: &impl Serialize) -> TestAccount {
+        let data = bincode::serialize(value).unwrap();
+        TestAccount::new(
+            pubkey,
+            AccountSharedData::create(lamports, data, sysvar::id(), false, 0),
+        )
+    }
+
+    fn clock(slot: Slot) -> Clock {
+        Clock {
+            slot,
+            ..Clock::default()
+        }
+    }
+
+    #[test]
+    fn decodes_latest_sysvars() {
+        let mut stake_history = StakeHistory::default();
+        stake_history.add(
+            4,
+            StakeHistoryEntry {
+                effective: 10,
+                activating: 2,
+                deactivating: 1,
+            },
+        );
+        let rent = Rent::default();
+        let files = vec![
+            (
+                3,
+                1,
+                vec![
+                    sysvar(sysvar::rent::ID, 1, &rent),
+                    sysvar(sysvar::clock::ID, 1, &clock(3)),
+                    sysvar(sysvar::stake_history::ID, 1, &stake_history),
+                ],
+            ),
+            // the same slot and write version, decided by the file id
+            (5, 2, vec![sysvar(sysvar::clock::ID, 1, &clock(5))]),
+            (5, 3, vec![sysvar(sysvar::clock::ID, 1, &clock(6))]),
+            (
+                5,
+                1,
+                vec![
+                    // closed
+                    sysvar(sysvar::rent::ID, 0, &rent),
+                    // not an EpochSchedule
+                    sysvar(sysvar::epoch_schedule::ID, 1, &[1u8, 2]),
+                ],
+            ),
+        ];
+        let expected = Sysvars {
+            clock: Some(clock(6)),
+            stake_history: Some(stake_history),
+            ..Sysvars::default()
+        };
+
+        let mut extractor = TestExtractor::new(files);
+        assert_eq!(Sysvars::from_extractor(&mut extractor).unwrap(), expected);
+        extractor.files.reverse();
+        assert_eq!(Sysvars::from_extractor(&mut extractor).unwrap(), expected);
+        let accounts = LatestAccounts::from_extractor(&mut extractor).unwrap();
+        assert_eq!(Sysvars::from_accounts(&accounts), expected);
+    }
+
+    #[test]
+    fn compares_with_bank_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let bank = new_child_bank(new_bank(), 3);
+        let archive = write_full_snapshot_archive(&bank, dir.path(), ArchiveFormat::Tar);
+        let mut extractor = ArchiveSnapshotExtractor::open(&archive).unwrap();
+        let mut sysvars = extractor.sysvars().unwrap();
+        let bank_fields = extractor.bank_fields();
+        assert_eq!(sysvars.clock.as_ref().unwrap().slot, 3);
+        assert_eq!(
+            sysvars.epoch_schedule.as_ref(),
+            Some(&bank_fields.epoch_schedule)
+        );
+        assert!(sysvars.rent.is_some());
+        assert_eq!(sysvars.verify(bank_fields), Vec::new());
+
+        sysvars.clock.as_mut().unwrap().slot = 2;
+        let mut epoch_schedule = bank_fields.epoch_schedule.clone();
+        epoch_schedule.slots_per_epoch += 1;
+        sysvars.epoch_schedule = Some(epoch_schedule.clone());
+        assert_eq!(
+            sysvars.verify(bank_fields),
+            vec![
+                SysvarMismatch::ClockSlot {
+                    sysvar: 2,
+                    manifest: 3,
+                },
+                SysvarMismatch::EpochSchedule {
+                    sysvar: epoch_schedule,
+                    manifest: bank_fields.epoch_schedule.clone(),
+                },
+            ]
+        );
+
+        let missing = Sysvars::default();
+        assert_eq!(
+            missing.verify(bank_fields),
+            vec![
+                SysvarMismatch::Missing(sysvar::clock::ID),
+                SysvarMismatch::Missing(sysvar::epoch_schedule::ID),
+            ]
+        );
+    }
+}
@@ -0,0 +1,323 @@
+use {
+    crate::append_vec::StoredAccountMeta,
+    solana_accounts_db::{
+        account_storage::meta::{AccountMeta, StoredMeta},
+        tiered_storage::{
+            file::FILE_MAGIC_NUMBER,
+            footer::{FOOTER_FORMAT_VERSION, FOOTER_SIZE, FOOTER_TAIL_SIZE},
+            hot::HotAccountMeta,
+            index::IndexOffset,
+            readable::TieredStorageReader,
+        },
+    },
+    solana_sdk::{account::ReadableAccount, hash::Hash, pubkey::Pubkey},
+    std::{
+        fs::OpenOptions,
+        io::{self, Read, Seek, SeekFrom, Write},
+        mem,
+        path::Path,
+    },
+};
+
+/// Account metas are stored at offsets in units of this alignment.
+const HOT_ACCOUNT_ALIGNMENT: usize = 8;
+
+/// Account hashes are not stored in tiered storage.
+static DEFAULT_ACCOUNT_HASH: Hash = Hash::new_from_array([0; 32]);
+
+/// Whether `data` ends with a tiered storage footer tail.
+pub fn is_tiered_storage(data: &[u8]) -> bool {
+    data.len() >= FOOTER_SIZE && is_footer_tail(&data[data.len() - FOOTER_TAIL_SIZE..])
+}
+
+/// Whether the file at `path` ends with a tiered storage footer tail.
+pub fn is_tiered_storage_file(path: impl AsRef<Path>) -> io::Result<bool> {
+    let mut file = OpenOptions::new().read(true).open(path)?;
+    if file.metadata()?.len() < FOOTER_SIZE as u64 {
+        return Ok(false);
+    }
+    let mut tail = [0u8; FOOTER_TAIL_SIZE];
+    file.seek(SeekFrom::End(-(FOOTER_TAIL_SIZE as i64)))?;
+    file.read_exact(&mut tail)?;
+    Ok(is_footer_tail(&tail))
+}
+
+/// Format version, footer size and magic number, which keep their place in every footer format.
+fn is_footer_tail(tail: &[u8]) -> bool {
+    read_u64(tail, 0) == Some(FOOTER_FORMAT_VERSION)
+        && read_u64(tail, 8) == Some(FOOTER_SIZE as u64)
+        && read_u64(tail, 16) == Some(FILE_MAGIC_NUMBER)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().expect("checked")))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset.checked_add(8)?)?;
+    Some(u64::from_le_bytes(bytes.try_into().expect("checked")))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// The metas of an account, which tiered storage does not store as such.
+struct TieredAccount {
+    meta: StoredMeta,
+    account_meta: AccountMeta,
+    data_offset: usize,
+    data_len: usize,
+    stored_size: usize,
+}
+
+/// A read-only tiered storage file, read with the tiered storage reader of the validator.
+///
+/// Accounts are addressed by their position in the index block of the file. Their metas are
+/// decoded when the file is opened, about 130 bytes per account, so that accounts can be
+/// lent as a [`StoredAccountMeta`] borrowing from the file like an AppendVec does.
+pub struct TieredStorageFile {
+    reader: TieredStorageReader,
+    accounts: Vec<TieredAccount>,
+    slot: u64,
+    id: u64,
+}
+
+impl TieredStorageFile {
+    pub fn new_from_file<P: AsRef<Path>>(path: P, slot: u64, id: u64) -> io::Result<Self> {
+        let reader = TieredStorageReader::new_from_path(path).map_err(io::Error::other)?;
+        Self::sanitize(&reader)?;
+        let accounts = (0..reader.num_accounts())
+            .map(|index| {
+                Self::read_account(&reader, index)
+                    .ok_or_else(|| invalid_data(format!("account {} can not be read", index)))
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(TieredStorageFile {
+            reader,
+            accounts,
+            slot,
+            id,
+        })
+    }
+
+    /// Reads a tiered storage file from memory. The reader maps files only, so the contents are
+    /// written to a temporary file, which is removed again once mapped.
+    pub fn new_from_slice(data: &[u8], slot: u64, id: u64) -> io::Result<Self> {
+        let mut file = tempfile::NamedTempFile::new()?;
+        file.write_all(data)?;
+        file.flush()?;
+        Self::new_from_file(file.path(), slot, id)
+    }
+
+    /// Checks the blocks and account offsets the reader relies on, as it panics on an account
+    /// meta beyond the account blocks.
+    fn sanitize(reader: &TieredStorageReader) -> io::Result<()> {
+        let footer = reader.footer();
+        let data = reader.data_for_archive();
+        let account_count = footer.account_entry_count as usize;
+        let index_block_offset = footer.index_block_offset as usize;
+        let offsets_offset = index_block_offset
+            .checked_add(account_count * mem::size_of::<Pubkey>())
+            .ok_or_else(|| invalid_data("index block exceeds the file".to_string()))?;
+        let index_block_end = offsets_offset.saturating_add(account_count * mem::size_of::<u32>());
+        let owners_block_end = (footer.owners_block_offset as usize)
+            .saturating_add(footer.owner_count as usize * mem::size_of::<Pubkey>());
+        if index_block_end > footer.owners_block_offset as usize
+            || owners_block_end > data.len().saturating_sub(FOOTER_SIZE)
+        {
+            return Err(invalid_data(format!(
+                "tiered storage blocks exceed the file size {}",
+                data.len()
+            )));
+        }
+        for index in 0..account_count {
+            let offset = read_u32(data, offsets_offset + index * mem::size_of::<u32>())
+                .expect("checked") as usize
+                * HOT_ACCOUNT_ALIGNMENT;
+            if offset + mem::size_of::<HotAccountMeta>() > index_block_offset {
+                return Err(invalid_data(format!(
+                    "account {} at offset {} exceeds the account blocks",
+                    index, offset
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The number of accounts stored in the file.
+    pub fn num_accounts(&self) -> usize {
+        self.accounts.len()
+    }
+
+    /// The size of the file in bytes.
+    pub fn len(&self) -> usize {
+        self.reader.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    fn read_account(reader: &TieredStorageReader, index: usize) -> Option<TieredAccount> {
+        // The reader lends accounts to a callback only, so the position of the data in the whole
+        // file is kept to borrow it again later.
+        let file = reader.data_for_archive();
+        reader
+            .get_stored_account_meta_callback(IndexOffset(index.try_into().ok()?), |account| {
+                let data = account.data();
+                Some(TieredAccount {
+                    meta: StoredMeta {
+                        write_version_obsolete: 0,
+                        data_len: data.len() as u64,
+                        pubkey: *account.pubkey(),
+                    },
+                    account_meta: AccountMeta {
+                        lamports: account.lamports(),
+                        rent_epoch: account.rent_epoch(),
+                        owner: *account.owner(),
+                        executable: account.executable(),
+                    },
+                    data_offset: (data.as_ptr() as usize).checked_sub(file.as_ptr() as usize)?,
+                    data_len: data.len(),
+                    stored_size: account.stored_size(),
+                })
+            })
+            .ok()??
+    }
+
+    /// Returns the account at `index` and the index of the next account, or None if `index`
+    /// is out of range.
+    pub fn get_account(&self, index: usize) -> Option<(StoredAccountMeta<'_>, usize)> {
+        let account = self.accounts.get(index)?;
+        let file = self.reader.data_for_archive();
+        Some((
+            StoredAccountMeta {
+                meta: &account.meta,
+                account_meta: &account.account_meta,
+                data: file.get(account.data_offset..account.data_offset + account.data_len)?,
+                offset: index,
+                stored_size: account.stored_size,
+                hash: &DEFAULT_ACCOUNT_HASH,
+            },
+            index + 1,
+        ))
+    }
+
+    pub const fn slot(&self) -> u64 {
+        self.slot
+    }
+
+    pub const fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{append_vec::AppendVec, append_vec_iter},
+        solana_accounts_db::tiered_storage::hot::HotStorageWriter,
+        solana_sdk::account::{AccountSharedData, WritableAccount},
+        std::fs,
+    };
+
+    fn accounts() -> Vec<(Pubkey, AccountSharedData)> {
+        let owner = Pubkey::new_unique();
+        let mut executable = AccountSharedData::new(1, 3, &Pubkey::new_unique());
+        executable.set_executable(true);
+        let mut rent_paying = AccountSharedData::new(2, 9, &owner);
+        rent_paying.set_rent_epoch(7);
+        rent_paying
+            .data_as_mut_slice()
+            .copy_from_slice(b"rent paid");
+        vec![
+            (Pubkey::new_unique(), AccountSharedData::new(10, 0, &owner)),
+            (Pubkey::new_unique(), executable),
+            (Pubkey::new_unique(), rent_paying),
+            (
+                Pubkey::new_unique(),
+                AccountSharedData::new(1000, 165, &owner),
+            ),
+        ]
+    }
+
+    fn write_hot_file(path: &Path, accounts: &[(Pubkey, AccountSharedData)]) {
+        let accounts: Vec<_> = accounts
+            .iter()
+            .map(|(pubkey, account)| (pubkey, account))
+            .collect();
+        let mut writer = HotStorageWriter::new(path).unwrap();
+        writer
+            .write_accounts(&(42, accounts.as_slice()), 0)
+            .unwrap();
+        writer.flush().unwrap();
+    }
+
+    fn assert_accounts(file: &AppendVec, expected: &[(Pubkey, AccountSharedData)]) {
+        assert_eq!((file.slot(), file.id()), (42, 7));
+        let stored: Vec<_> = append_vec_iter(file)
+            .map(|handle| handle.access().unwrap())
+            .collect();
+        assert_eq!(stored.len(), expected.len());
+        for (index, (stored, (pubkey, account))) in stored.iter().zip(expected).enumerate() {
+            assert_eq!(stored.offset, index);
+            assert_eq!(stored.meta.pubkey, *pubkey);
+            assert_eq!(*stored.hash, Hash::default());
+            assert_eq!(stored.clone_account(), *account);
+        }
+    }
+
+    #[test]
+    fn reads_hot_storage_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("42.7");
+        let accounts = accounts();
+        write_hot_file(&path, &accounts);
+
+        assert!(is_tiered_storage_file(&path).unwrap());
+        let file = AppendVec::new_from_file(&path, 0, 42, 7).unwrap();
+        assert!(file.tiered_storage().is_some());
+        assert_accounts(&file, &accounts);
+
+        let data = fs::read(&path).unwrap();
+        let file = AppendVec::new_from_reader(&mut data.as_slice(), data.len(), 42, 7).unwrap();
+        assert!(file.tiered_storage().is_some());
+        assert_accounts(&file, &accounts);
+        assert_eq!(file.get_account(accounts.len()).map(|(_, next)| next), None);
+    }
+
+    #[test]
+    fn rejects_account_offsets_beyond_account_blocks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("42.7");
+        write_hot_file(&path, &accounts());
+        let mut data = fs::read(&path).unwrap();
+        let footer = *TieredStorageReader::new_from_path(&path).unwrap().footer();
+        let last_offset = footer.index_block_offset as usize
+            + footer.account_entry_count as usize * (mem::size_of::<Pubkey>() + 4)
+            - 4;
+        data[last_offset..last_offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let error = TieredStorageFile::new_from_slice(&data, 42, 7)
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn detects_footer_tail() {
+        assert!(!is_tiered_storage(&[]));
+        assert!(!is_tiered_storage(&[0; FOOTER_SIZE]));
+        let mut data = vec![0; FOOTER_SIZE];
+        data[FOOTER_SIZE - 24..FOOTER_SIZE - 16]
+            .copy_from_slice(&FOOTER_FORMAT_VERSION.to_le_bytes());
+        data[FOOTER_SIZE - 16..FOOTER_SIZE - 8]
+            .copy_from_slice(&(FOOTER_SIZE as u64).to_le_bytes());
+        data[FOOTER_SIZE - 8..].copy_from_slice(&FILE_MAGIC_NUMBER.to_le_bytes());
+        assert!(is_tiered_storage(&data));
+        assert!(!is_tiered_storage(&data[1..]));
+    }
+}
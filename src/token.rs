@@ -0,0 +1,476 @@
+use {
+    solana_sdk::{account::ReadableAccount, program_pack::Pack, pubkey::Pubkey},
+    spl_token_2022::{
+        extension::{
+            confidential_mint_burn::ConfidentialMintBurn,
+            confidential_transfer::{ConfidentialTransferAccount, ConfidentialTransferMint},
+            confidential_transfer_fee::{
+                ConfidentialTransferFeeAmount, ConfidentialTransferFeeConfig,
+            },
+            cpi_guard::CpiGuard,
+            default_account_state::DefaultAccountState,
+            group_member_pointer::GroupMemberPointer,
+            group_pointer::GroupPointer,
+            interest_bearing_mint::InterestBearingConfig,
+            memo_transfer::MemoTransfer,
+            metadata_pointer::MetadataPointer,
+            mint_close_authority::MintCloseAuthority,
+            pausable::{PausableAccount, PausableConfig},
+            permanent_delegate::PermanentDelegate,
+            scaled_ui_amount::ScaledUiAmountConfig,
+            transfer_fee::{TransferFeeAmount, TransferFeeConfig},
+            transfer_hook::{TransferHook, TransferHookAccount},
+            BaseState, BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+        },
+        state::{Account, Mint},
+    },
+    spl_token_group_interface::state::{TokenGroup, TokenGroupMember},
+    spl_token_metadata_interface::state::TokenMetadata,
+};
+
+/// The program owning a token account.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokenProgram {
+    Token,
+    Token2022,
+}
+
+impl TokenProgram {
+    pub fn from_owner(owner: &Pubkey) -> Option<Self> {
+        if *owner == spl_token::id() {
+            Some(TokenProgram::Token)
+        } else if *owner == spl_token_2022::id() {
+            Some(TokenProgram::Token2022)
+        } else {
+            None
+        }
+    }
+
+    pub const fn id(self) -> Pubkey {
+        match self {
+            TokenProgram::Token => spl_token::id(),
+            TokenProgram::Token2022 => spl_token_2022::id(),
+        }
+    }
+}
+
+/// A Token-2022 extension of a mint or token account.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenExtension {
+    TransferFeeConfig(TransferFeeConfig),
+    TransferFeeAmount(TransferFeeAmount),
+    MintCloseAuthority(MintCloseAuthority),
+    ConfidentialTransferMint(ConfidentialTransferMint),
+    ConfidentialTransferAccount(Box<ConfidentialTransferAccount>),
+    DefaultAccountState(DefaultAccountState),
+    ImmutableOwner,
+    MemoTransfer(MemoTransfer),
+    NonTransferable,
+    InterestBearingConfig(InterestBearingConfig),
+    CpiGuard(CpiGuard),
+    PermanentDelegate(PermanentDelegate),
+    NonTransferableAccount,
+    TransferHook(TransferHook),
+    TransferHookAccount(TransferHookAccount),
+    ConfidentialTransferFeeConfig(ConfidentialTransferFeeConfig),
+    ConfidentialTransferFeeAmount(ConfidentialTransferFeeAmount),
+    MetadataPointer(MetadataPointer),
+    TokenMetadata(TokenMetadata),
+    GroupPointer(GroupPointer),
+    TokenGroup(TokenGroup),
+    GroupMemberPointer(GroupMemberPointer),
+    TokenGroupMember(TokenGroupMember),
+    ConfidentialMintBurn(Box<ConfidentialMintBurn>),
+    ScaledUiAmount(ScaledUiAmountConfig),
+    Pausable(PausableConfig),
+    PausableAccount(PausableAccount),
+    /// A known extension whose data could not be decoded.
+    Invalid(ExtensionType),
+    /// An extension type this version does not know.
+    Unknown(u16),
+}
+
+/// A mint of the Token or Token-2022 program.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenMint {
+    pub program: TokenProgram,
+    pub mint: Mint,
+    pub extensions: Vec<TokenExtension>,
+}
+
+/// A token account of the Token or Token-2022 program.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenAccount {
+    pub program: TokenProgram,
+    pub account: Account,
+    pub extensions: Vec<TokenExtension>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenAccountData {
+    Mint(TokenMint),
+    Account(TokenAccount),
+}
+
+impl TokenMint {
+    pub fn transfer_fee_config(&self) -> Option<&TransferFeeConfig> {
+        self.extensions
+            .iter()
+            .find_map(|extension| match extension {
+                TokenExtension::TransferFeeConfig(config) => Some(config),
+                _ => None,
+            })
+    }
+
+    pub fn metadata_pointer(&self) -> Option<&MetadataPointer> {
+        self.extensions
+            .iter()
+            .find_map(|extension| match extension {
+                TokenExtension::MetadataPointer(pointer) => Some(pointer),
+                _ => None,
+            })
+    }
+
+    pub fn token_metadata(&self) -> Option<&TokenMetadata> {
+        self.extensions
+            .iter()
+            .find_map(|extension| match extension {
+                TokenExtension::TokenMetadata(metadata) => Some(metadata),
+                _ => None,
+            })
+    }
+}
+
+/// Decodes mints and token accounts owned by the Token or Token-2022 program, including the
+/// extensions of Token-2022 accounts. Returns None for other and uninitialized accounts.
+pub fn decode_token_account(account: &impl ReadableAccount) -> Option<TokenAccountData> {
+    let program = TokenProgram::from_owner(account.owner())?;
+    let data = account.data();
+    if program == TokenProgram::Token && data.len() != Mint::LEN && data.len() != Account::LEN {
+        return None;
+    }
+    if let Ok(state) = StateWithExtensions::<Account>::unpack(data) {
+        return Some(TokenAccountData::Account(TokenAccount {
+            program,
+            account: state.base,
+            extensions: decode_extensions(&state),
+        }));
+    }
+    if let Ok(state) = StateWithExtensions::<Mint>::unpack(data) {
+        return Some(TokenAccountData::Mint(TokenMint {
+            program,
+            mint: state.base,
+            extensions: decode_extensions(&state),
+        }));
+    }
+    None
+}
+
+/// Decodes a mint, returning None if the account is not an initialized mint.
+pub fn decode_mint(account: &impl ReadableAccount) -> Option<TokenMint> {
+    match decode_token_account(account)? {
+        TokenAccountData::Mint(mint) => Some(mint),
+        TokenAccountData::Account(_) => None,
+    }
+}
+
+/// Decodes a token account, returning None if the account is not an initialized token account.
+pub fn decode_token(account: &impl ReadableAccount) -> Option<TokenAccount> {
+    match decode_token_account(account)? {
+        TokenAccountData::Account(token) => Some(token),
+        TokenAccountData::Mint(_) => None,
+    }
+}
+
+/// Decodes all extensions in the order they are stored.
+fn decode_extensions<S: BaseState + Pack>(state: &StateWithExtensions<S>) -> Vec<TokenExtension> {
+    extension_types(state.get_tlv_data())
+        .into_iter()
+        .map(
+            |extension_type| match ExtensionType::try_from(extension_type) {
+                Ok(extension_type) => decode_extension(state, extension_type)
+                    .unwrap_or(TokenExtension::Invalid(extension_type)),
+                Err(_) => TokenExtension::Unknown(extension_type),
+            },
+        )
+        .collect()
+}
+
+/// Walks the type-length-value entries up to the first uninitialized entry.
+fn extension_types(tlv_data: &[u8]) -> Vec<u16> {
+    let mut types = Vec::new();
+    let mut offset = 0;
+    while let Some(header) = tlv_data.get(offset..offset + 4) {
+        let extension_type = u16::from_le_bytes([header[0], header[1]]);
+        let length = u16::from_le_bytes([header[2], header[3]]) as usize;
+        if extension_type == u16::from(ExtensionType::Uninitialized) {
+            break;
+        }
+        types.push(extension_type);
+        offset += 4 + length;
+    }
+    types
+}
+
+fn decode_extension<S: BaseState + Pack>(
+    state: &StateWithExtensions<S>,
+    extension_type: ExtensionType,
+) -> Option<TokenExtension> {
+    macro_rules! get {
+        ($extension:ty) => {
+            *state.get_extension::<$extension>().ok()?
+        };
+    }
+
+    Some(match extension_type {
+        ExtensionType::TransferFeeConfig => {
+            TokenExtension::TransferFeeConfig(get!(TransferFeeConfig))
+        }
+        ExtensionType::TransferFeeAmount => {
+            TokenExtension::TransferFeeAmount(get!(TransferFeeAmount))
+        }
+        ExtensionType::MintCloseAuthority => {
+            TokenExtension::MintCloseAuthority(get!(MintCloseAuthority))
+        }
+        ExtensionType::ConfidentialTransferMint => {
+            TokenExtension::ConfidentialTransferMint(get!(ConfidentialTransferMint))
+        }
+        ExtensionType::ConfidentialTransferAccount => {
+            TokenExtension::ConfidentialTransferAccount(Box::new(get!(ConfidentialTransferAccount)))
+        }
+        ExtensionType::DefaultAccountState => {
+            TokenExtension::DefaultAccountState(get!(DefaultAccountState))
+        }
+        ExtensionType::ImmutableOwner => TokenExtension::ImmutableOwner,
+        ExtensionType::MemoTransfer => TokenExtension::MemoTransfer(get!(MemoTransfer)),
+        ExtensionType::NonTransferable => TokenExtension::NonTransferable,
+        ExtensionType::InterestBearingConfig => {
+            TokenExtension::InterestBearingConfig(get!(InterestBearingConfig))
+        }
+        ExtensionType::CpiGuard => TokenExtension::CpiGuard(get!(CpiGuard)),
+        ExtensionType::PermanentDelegate => {
+            TokenExtension::PermanentDelegate(get!(PermanentDelegate))
+        }
+        ExtensionType::NonTransferableAccount => TokenExtension::NonTransferableAccount,
+        ExtensionType::TransferHook => TokenExtension::TransferHook(get!(TransferHook)),
+        ExtensionType::TransferHookAccount => {
+            TokenExtension::TransferHookAccount(get!(TransferHookAccount))
+        }
+        ExtensionType::ConfidentialTransferFeeConfig => {
+            TokenExtension::ConfidentialTransferFeeConfig(get!(ConfidentialTransferFeeConfig))
+        }
+        ExtensionType::ConfidentialTransferFeeAmount => {
+            TokenExtension::ConfidentialTransferFeeAmount(get!(ConfidentialTransferFeeAmount))
+        }
+        ExtensionType::MetadataPointer => TokenExtension::MetadataPointer(get!(MetadataPointer)),
+        ExtensionType::TokenMetadata => {
+            TokenExtension::TokenMetadata(state.get_variable_len_extension::<TokenMetadata>().ok()?)
+        }
+        ExtensionType::GroupPointer => TokenExtension::GroupPointer(get!(GroupPointer)),
+        ExtensionType::TokenGroup => TokenExtension::TokenGroup(get!(TokenGroup)),
+        ExtensionType::GroupMemberPointer => {
+            TokenExtension::GroupMemberPointer(get!(GroupMemberPointer))
+        }
+        ExtensionType::TokenGroupMember => TokenExtension::TokenGroupMember(get!(TokenGroupMember)),
+        ExtensionType::ConfidentialMintBurn => {
+            TokenExtension::ConfidentialMintBurn(Box::new(get!(ConfidentialMintBurn)))
+        }
+        ExtensionType::ScaledUiAmount => TokenExtension::ScaledUiAmount(get!(ScaledUiAmountConfig)),
+        ExtensionType::Pausable => TokenExtension::Pausable(get!(PausableConfig)),
+        ExtensionType::PausableAccount => TokenExtension::PausableAccount(get!(PausableAccount)),
+        ExtensionType::Uninitialized => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_sdk::{account::Account as SolanaAccount, program_option::COption},
+        spl_token_2022::{
+            extension::{BaseStateWithExtensionsMut, StateWithExtensionsMut},
+            state::{AccountState, Multisig},
+        },
+    };
+
+    fn account(program: TokenProgram, data: Vec<u8>) -> SolanaAccount {
+        SolanaAccount {
+            lamports: 1,
+            data,
+            owner: program.id(),
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    fn mint() -> Mint {
+        Mint {
+            mint_authority: COption::Some(Pubkey::new_unique()),
+            supply: 1_000,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        }
+    }
+
+    fn token() -> Account {
+        Account {
+            mint: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            amount: 42,
+            state: AccountState::Initialized,
+            ..Account::default()
+        }
+    }
+
+    fn packed<S: Pack>(state: S) -> Vec<u8> {
+        let mut data = vec![0; S::LEN];
+        S::pack(state, &mut data).unwrap();
+        data
+    }
+
+    /// A Token-2022 account followed by the raw type-length-value entries in `tlv`.
+    fn token_with_tlv(token: Account, tlv: &[u8]) -> Vec<u8> {
+        let mut data = packed(token);
+        data.push(2); // AccountType::Account
+        data.extend_from_slice(tlv);
+        data
+    }
+
+    #[test]
+    fn decodes_legacy_mint_and_account() {
+        let mint = mint();
+        let decoded = decode_mint(&account(TokenProgram::Token, packed(mint))).unwrap();
+        assert_eq!(decoded.program, TokenProgram::Token);
+        assert_eq!(decoded.mint, mint);
+        assert!(decoded.extensions.is_empty());
+
+        let token = token();
+        let decoded = decode_token(&account(TokenProgram::Token, packed(token))).unwrap();
+        assert_eq!(decoded.program, TokenProgram::Token);
+        assert_eq!(decoded.account, token);
+        assert!(decoded.extensions.is_empty());
+
+        assert!(decode_token(&account(TokenProgram::Token, packed(mint))).is_none());
+        let mut not_token = account(TokenProgram::Token, packed(token));
+        not_token.owner = Pubkey::new_unique();
+        assert!(decode_token_account(&not_token).is_none());
+    }
+
+    #[test]
+    fn decodes_token_2022_mint_extensions() {
+        let mint = mint();
+        let mint_pubkey = Pubkey::new_unique();
+        let metadata = TokenMetadata {
+            update_authority: Some(Pubkey::new_unique()).try_into().unwrap(),
+            mint: mint_pubkey,
+            name: "Token".to_string(),
+            symbol: "TKN".to_string(),
+            uri: "https://example.com/token.json".to_string(),
+            additional_metadata: vec![("key".to_string(), "value".to_string())],
+        };
+        let len = ExtensionType::try_calculate_account_len::<Mint>(&[
+            ExtensionType::TransferFeeConfig,
+            ExtensionType::MetadataPointer,
+        ])
+        .unwrap()
+            + metadata.tlv_size_of().unwrap();
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        let transfer_fee = state.init_extension::<TransferFeeConfig>(false).unwrap();
+        transfer_fee.newer_transfer_fee.transfer_fee_basis_points = 50.into();
+        transfer_fee.newer_transfer_fee.maximum_fee = 5_000.into();
+        let transfer_fee = *transfer_fee;
+        let pointer = state.init_extension::<MetadataPointer>(false).unwrap();
+        pointer.metadata_address = Some(mint_pubkey).try_into().unwrap();
+        let pointer = *pointer;
+        state.init_variable_len_extension(&metadata, false).unwrap();
+        state.base = mint;
+        state.pack_base();
+        state.init_account_type().unwrap();
+
+        let decoded = decode_mint(&account(TokenProgram::Token2022, data)).unwrap();
+        assert_eq!(decoded.program, TokenProgram::Token2022);
+        assert_eq!(decoded.mint, mint);
+        assert_eq!(
+            decoded.extensions,
+            vec![
+                TokenExtension::TransferFeeConfig(transfer_fee),
+                TokenExtension::MetadataPointer(pointer),
+                TokenExtension::TokenMetadata(metadata.clone()),
+            ]
+        );
+        assert_eq!(decoded.transfer_fee_config(), Some(&transfer_fee));
+        assert_eq!(decoded.metadata_pointer(), Some(&pointer));
+        assert_eq!(decoded.token_metadata(), Some(&metadata));
+    }
+
+    #[test]
+    fn ignores_multisig_and_uninitialized_accounts() {
+        let multisig = Multisig {
+            m: 1,
+            n: 1,
+            is_initialized: true,
+            ..Multisig::default()
+        };
+        for program in [TokenProgram::Token, TokenProgram::Token2022] {
+            assert!(decode_token_account(&account(program, packed(multisig))).is_none());
+            for len in [Mint::LEN, Account::LEN] {
+                assert!(decode_token_account(&account(program, vec![0; len])).is_none());
+            }
+            assert!(decode_token_account(&account(program, Vec::new())).is_none());
+        }
+    }
+
+    #[test]
+    fn reports_malformed_extensions() {
+        let token = token();
+        let transfer_fee_amount = u16::from(ExtensionType::TransferFeeAmount).to_le_bytes();
+
+        // an entry shorter than its extension, followed by an unknown extension type
+        let mut tlv = Vec::new();
+        tlv.extend_from_slice(&transfer_fee_amount);
+        tlv.extend_from_slice(&4u16.to_le_bytes());
+        tlv.extend_from_slice(&[0; 4]);
+        tlv.extend_from_slice(&u16::MAX.to_le_bytes());
+        tlv.extend_from_slice(&0u16.to_le_bytes());
+        let decoded = decode_token(&account(
+            TokenProgram::Token2022,
+            token_with_tlv(token, &tlv),
+        ))
+        .unwrap();
+        assert_eq!(decoded.account, token);
+        assert_eq!(
+            decoded.extensions,
+            vec![
+                TokenExtension::Invalid(ExtensionType::TransferFeeAmount),
+                TokenExtension::Unknown(u16::MAX),
+            ]
+        );
+
+        // an entry running past the end of the data, and a truncated entry header
+        let mut tlv = Vec::new();
+        tlv.extend_from_slice(&transfer_fee_amount);
+        tlv.extend_from_slice(&100u16.to_le_bytes());
+        tlv.extend_from_slice(&[0; 4]);
+        let decoded: Vec<_> = [&tlv[..], &transfer_fee_amount[..]]
+            .into_iter()
+            .map(|tlv| {
+                decode_token(&account(
+                    TokenProgram::Token2022,
+                    token_with_tlv(token, tlv),
+                ))
+                .map(|token| token.extensions)
+            })
+            .collect();
+        assert_eq!(
+            decoded,
+            vec![
+                Some(vec![TokenExtension::Invalid(
+                    ExtensionType::TransferFeeAmount
+                )]),
+                Some(Vec::new()),
+            ]
+        );
+    }
+}
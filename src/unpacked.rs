@@ -1,8 +1,9 @@
 use {
     crate::{
         deserialize_from, parse_append_vec_name, AccountsDbFields, AppendVec, AppendVecIterator,
-        DeserializableVersionedBank, ReadProgressTracking, SerializableAccountStorageEntry,
-        SnapshotError, SnapshotExtractor, SnapshotResult, SNAPSHOTS_DIR,
+        DeserializableVersionedBank, ExtraFieldsToDeserialize, ReadProgressTracking,
+        SerializableAccountStorageEntry, SnapshotError, SnapshotExtractor, SnapshotResult,
+        SNAPSHOTS_DIR,
     },
     itertools::Itertools,
     log::info,
@@ -19,13 +20,27 @@ use {
 /// Extracts account data from snapshots that were unarchived to a file system.
 pub struct UnpackedSnapshotExtractor {
     root: PathBuf,
+    bank_fields: DeserializableVersionedBank,
     accounts_db_fields: AccountsDbFields<SerializableAccountStorageEntry>,
+    extra_fields: ExtraFieldsToDeserialize,
 }
 
 impl SnapshotExtractor for UnpackedSnapshotExtractor {
     fn iter(&mut self) -> AppendVecIterator<'_> {
         Box::new(self.unboxed_iter())
     }
+
+    fn bank_fields(&self) -> &DeserializableVersionedBank {
+        &self.bank_fields
+    }
+
+    fn accounts_db_fields(&self) -> &AccountsDbFields<SerializableAccountStorageEntry> {
+        &self.accounts_db_fields
+    }
+
+    fn extra_fields(&self) -> &ExtraFieldsToDeserialize {
+        &self.extra_fields
+    }
 }
 
 impl UnpackedSnapshotExtractor {
@@ -59,13 +74,17 @@ impl UnpackedSnapshotExtractor {
         let mut snapshot_file = BufReader::new(snapshot_file);
 
         let pre_unpack = Instant::now();
-        let versioned_bank: DeserializableVersionedBank = deserialize_from(&mut snapshot_file)?;
-        drop(versioned_bank);
+        let mut bank_fields: DeserializableVersionedBank = deserialize_from(&mut snapshot_file)?;
         let versioned_bank_post_time = Instant::now();
 
         let accounts_db_fields: AccountsDbFields<SerializableAccountStorageEntry> =
             deserialize_from(&mut snapshot_file)?;
         let accounts_db_fields_post_time = Instant::now();
+
+        let extra_fields: ExtraFieldsToDeserialize = deserialize_from(&mut snapshot_file)?;
+        bank_fields.fee_rate_governor = bank_fields
+            .fee_rate_governor
+            .clone_with_lamports_per_signature(extra_fields.lamports_per_signature);
         drop(snapshot_file);
 
         info!(
@@ -79,7 +98,9 @@ impl UnpackedSnapshotExtractor {
 
         Ok(UnpackedSnapshotExtractor {
             root: path.to_path_buf(),
+            bank_fields,
             accounts_db_fields,
+            extra_fields,
         })
     }
 
@@ -103,6 +124,12 @@ impl UnpackedSnapshotExtractor {
             }))
     }
 
+    /// Opens the accounts file `<slot>.<id>` of the snapshot.
+    pub fn open_accounts_file(&self, slot: u64, id: u64) -> SnapshotResult<AppendVec> {
+        let path = self.root.join("accounts").join(format!("{}.{}", slot, id));
+        self.open_append_vec(slot, id, &path)
+    }
+
     fn open_append_vec(&self, slot: u64, id: u64, path: &Path) -> SnapshotResult<AppendVec> {
         let known_vecs = self
             .accounts_db_fields
@@ -120,6 +147,7 @@ impl UnpackedSnapshotExtractor {
             path,
             known_vec.accounts_current_len,
             slot,
+            id,
         )?)
     }
 }
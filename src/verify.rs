@@ -0,0 +1,163 @@
+use {
+    crate::{
+        append_vec::{AppendVec, StoredAccountMeta},
+        append_vec_iter,
+    },
+    log::{info, warn},
+    solana_accounts_db::accounts_db::AccountsDb,
+    solana_sdk::{hash::Hash, pubkey::Pubkey},
+    std::fmt,
+};
+
+/// Recomputes the hash of an account the way the runtime does, over its lamports, rent epoch,
+/// data, executable flag, owner and pubkey. Zero-lamport accounts hash to the default hash.
+pub fn account_hash(stored: &StoredAccountMeta) -> Hash {
+    AccountsDb::hash_account(stored, &stored.meta.pubkey).0
+}
+
+/// An account whose stored hash does not match its contents.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountHashMismatch {
+    pub slot: u64,
+    pub id: u64,
+    pub offset: usize,
+    pub pubkey: Pubkey,
+    pub stored: Hash,
+    pub computed: Hash,
+}
+
+impl fmt::Display for AccountHashMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "account {} in AppendVec {}.{} at offset {}: stored hash {}, computed {}",
+            self.pubkey, self.slot, self.id, self.offset, self.stored, self.computed
+        )
+    }
+}
+
+/// Outcome of checking the stored hashes of a set of accounts.
+#[derive(Clone, Debug, Default)]
+pub struct AccountHashReport {
+    /// accounts whose stored hash was compared with the recomputed one
+    pub checked: u64,
+    /// accounts stored without a hash, as newer validators do
+    pub skipped: u64,
+    pub mismatches: Vec<AccountHashMismatch>,
+}
+
+impl AccountHashReport {
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    fn merge(&mut self, other: AccountHashReport) {
+        self.checked += other.checked;
+        self.skipped += other.skipped;
+        self.mismatches.extend(other.mismatches);
+    }
+}
+
+/// Checks the stored hash of every account in the accounts file.
+/// Tiered storage does not store account hashes, so its accounts are all skipped.
+pub fn verify_append_vec(append_vec: &AppendVec) -> AccountHashReport {
+    let mut report = AccountHashReport::default();
+    for handle in append_vec_iter(append_vec) {
+        let Some(stored) = handle.access() else {
+            continue;
+        };
+        if *stored.hash == Hash::default() {
+            report.skipped += 1;
+            continue;
+        }
+        report.checked += 1;
+        let computed = account_hash(&stored);
+        if computed != *stored.hash {
+            report.mismatches.push(AccountHashMismatch {
+                slot: append_vec.slot(),
+                id: append_vec.id(),
+                offset: stored.offset,
+                pubkey: stored.meta.pubkey,
+                stored: *stored.hash,
+                computed,
+            });
+        }
+    }
+    report
+}
+
+/// Checks the stored hash of every account copy in the accounts files, such as all files of a
+/// snapshot from [`LatestAccounts::append_vecs`](crate::latest::LatestAccounts::append_vecs).
+pub fn verify_account_hashes<'a>(
+    append_vecs: impl IntoIterator<Item = &'a AppendVec>,
+) -> AccountHashReport {
+    let mut report = AccountHashReport::default();
+    for append_vec in append_vecs {
+        let append_vec_report = verify_append_vec(append_vec);
+        for mismatch in &append_vec_report.mismatches {
+            warn!("Hash mismatch: {}", mismatch);
+        }
+        report.merge(append_vec_report);
+    }
+    info!(
+        "Checked {} account hashes ({} without stored hash), {} mismatches",
+        report.checked,
+        report.skipped,
+        report.mismatches.len()
+    );
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::append_vec::test_utils::{new_append_vec, TestAccount},
+        solana_sdk::account::AccountSharedData,
+    };
+
+    fn hashed(data: &[u8]) -> TestAccount {
+        let pubkey = Pubkey::new_unique();
+        let mut account = AccountSharedData::new(10, data.len(), &Pubkey::new_unique());
+        account.set_data_from_slice(data);
+        TestAccount {
+            hash: AccountsDb::hash_account(&account, &pubkey).0,
+            ..TestAccount::new(pubkey, account)
+        }
+    }
+
+    #[test]
+    fn reports_corrupted_account_hash() {
+        let mut corrupted = hashed(&[1, 2, 3]);
+        let stored = Hash::new_unique();
+        corrupted.hash = stored;
+        let without_hash = TestAccount::new(
+            Pubkey::new_unique(),
+            AccountSharedData::new(1, 0, &Pubkey::default()),
+        );
+        let accounts = [
+            hashed(&[]),
+            corrupted.clone(),
+            hashed(&[4; 20]),
+            without_hash,
+        ];
+        let append_vec = new_append_vec(7, 3, &accounts);
+
+        let report = verify_account_hashes([&append_vec]);
+        assert_eq!(report.checked, 3);
+        assert_eq!(report.skipped, 1);
+        assert_eq!(
+            report.mismatches,
+            vec![AccountHashMismatch {
+                slot: 7,
+                id: 3,
+                // one account without data before it
+                offset: 136,
+                pubkey: corrupted.pubkey,
+                stored,
+                computed: AccountsDb::hash_account(&corrupted.account, &corrupted.pubkey).0,
+            }]
+        );
+        assert!(!report.is_ok());
+    }
+}